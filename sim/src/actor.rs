@@ -1,7 +1,11 @@
 pub mod action;
+pub mod animation_lock;
 pub mod apply;
+pub mod auto_attack;
 pub mod calc;
+pub mod casting;
 pub mod damage;
+pub mod job_gauge;
 pub mod recast_expirations;
 pub mod rotation;
 pub mod stat;
@@ -13,6 +17,7 @@ use apply::Apply;
 use bevy_ecs::prelude::{Entity, Query};
 use calc::lookup::Job;
 use damage::Damage;
+use job_gauge::JobGauge;
 use recast_expirations::RecastExpirations;
 use rotation::Rotation;
 use stat::Stats;
@@ -28,6 +33,7 @@ pub type ActorTuple = (
     &'static mut StatusEffects,
     &'static mut Stats,
     &'static mut ActiveCombos,
+    &'static mut JobGauge,
 );
 
 pub type QueryActor<'a> = Query<'a, ActorTuple>;
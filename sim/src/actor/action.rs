@@ -1,12 +1,15 @@
 use super::apply::Apply;
+use super::calc;
+use super::stat::{SpecialStat, Stat, Stats};
 use super::QueryActor;
-use crate::sim::SimState;
+use crate::combat_log::CombatLog;
+use crate::sim::{SimState, SimTime};
 use bevy_ecs::prelude::Entity;
 use delegate::delegate;
 use std::collections::HashMap;
 use std::sync::Arc;
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Actions(HashMap<u32, Action>);
 
 impl Actions {
@@ -14,6 +17,11 @@ impl Actions {
         self.0.insert(action.id, action);
     }
 
+    // find looks an action up by its name.
+    pub fn find(&self, name: &str) -> Option<&Action> {
+        self.0.values().find(|action| action.name == name)
+    }
+
     delegate! {
         to self.0 {
             pub fn get(&self, id: &u32) -> Option<&Action>;
@@ -21,22 +29,143 @@ impl Actions {
     }
 }
 
-#[derive(Default, Clone)]
+/// CastTime is how long an action is cast before its results apply.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CastTime {
+    pub base: SimTime,
+    // speed is the stat that shortens the cast, like the GCD.
+    pub speed: Stat,
+}
+
+// DEFAULT_ANIMATION_LOCK is how long most actions stop the actor from acting again.
+pub const DEFAULT_ANIMATION_LOCK: SimTime = 600;
+
+#[derive(Clone)]
 pub struct Action {
     pub id: u32,
-    pub name: &'static str,
+    pub name: String,
     // oGCD indicates this action is off the global cooldown
     pub ogcd: bool,
+    // cast_time is None for instant actions.
+    pub cast_time: Option<CastTime>,
+    // animation_lock is how long the action stops the actor from acting again, before latency.
+    pub animation_lock: SimTime,
     pub results: Vec<Arc<dyn Apply + Send + Sync>>,
 }
 
+impl Default for Action {
+    fn default() -> Self {
+        Action {
+            id: 0,
+            name: String::new(),
+            ogcd: false,
+            cast_time: None,
+            animation_lock: DEFAULT_ANIMATION_LOCK,
+            results: vec![],
+        }
+    }
+}
+
 impl Action {
-    pub fn perform(&self, sim: &SimState, query: &mut QueryActor, source: Entity, target: Entity) {
+    pub fn perform(
+        &self,
+        sim: &SimState,
+        log: &mut CombatLog,
+        query: &mut QueryActor,
+        source: Entity,
+        target: Entity,
+    ) {
         for result in &self.results {
-            result.apply(sim, query, source, target);
+            result.apply(sim, log, query, source, target);
+        }
+    }
+
+    // start_cast applies the results that happen when a cast starts, eg. the GCD.
+    pub fn start_cast(
+        &self,
+        sim: &SimState,
+        log: &mut CombatLog,
+        query: &mut QueryActor,
+        source: Entity,
+        target: Entity,
+    ) {
+        for result in self.results.iter().filter(|result| result.at_cast_start()) {
+            result.apply(sim, log, query, source, target);
+        }
+    }
+
+    // complete_cast applies the rest of the results once the cast completes.
+    pub fn complete_cast(
+        &self,
+        sim: &SimState,
+        log: &mut CombatLog,
+        query: &mut QueryActor,
+        source: Entity,
+        target: Entity,
+    ) {
+        for result in self.results.iter().filter(|result| !result.at_cast_start()) {
+            result.apply(sim, log, query, source, target);
+        }
+    }
+
+    // is_instant_cast is true when a status makes an action with a cast time instant, eg. Swiftcast.
+    pub fn is_instant_cast(&self, stats: &Stats) -> bool {
+        self.cast_time.is_some() && stats.get_special(SpecialStat::InstantCast).is_some()
+    }
+
+    // cast_duration is how long the action takes to cast with the actor's current stats, or 0 for
+    // instant actions.
+    pub fn cast_duration(&self, stats: &Stats) -> SimTime {
+        match self.cast_time {
+            Some(_) if self.is_instant_cast(stats) => 0,
+            Some(cast_time) => {
+                let duration = calc::gcd(
+                    cast_time.base,
+                    stats.get(cast_time.speed),
+                    stats.get(Stat::Haste),
+                );
+                // Reductions are a percent of the cast, eg. 50 for a cast made twice as fast.
+                let reduction = stats.get(Stat::CastTimeReduction).clamp(0, 100) as SimTime;
+                duration * (100 - reduction) / 100
+            }
+            None => 0,
         }
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find() {
+        let mut actions = Actions::default();
+        actions.add(Action {
+            id: 1,
+            name: "True Thrust".into(),
+            ..Default::default()
+        });
+        assert_eq!(1, actions.find("True Thrust").unwrap().id);
+        assert!(actions.find("Vorpal Thrust").is_none());
+    }
+
+    #[test]
+    fn cast_duration() {
+        let mut action = Action::default();
+        let mut stats = Stats::default();
+        assert_eq!(0, action.cast_duration(&stats));
+        action.cast_time = Some(CastTime {
+            base: 1500,
+            speed: Stat::SpellSpeed,
+        });
+        assert_eq!(1500, action.cast_duration(&stats));
+        stats.set_base(Stat::SpellSpeed, 1320);
+        assert_eq!(1440, action.cast_duration(&stats));
+        stats.add(Stat::CastTimeReduction, 50);
+        assert_eq!(720, action.cast_duration(&stats));
+        assert!(!action.is_instant_cast(&stats));
+        stats.set_special(SpecialStat::InstantCast, 1);
+        assert!(action.is_instant_cast(&stats));
+        assert_eq!(0, action.cast_duration(&stats));
+    }
+}
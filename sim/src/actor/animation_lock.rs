@@ -0,0 +1,30 @@
+use crate::sim::SimTime;
+
+/// AnimationLock is when an actor's last action stops locking it. The actor can't act until then.
+#[derive(Debug, Default)]
+pub struct AnimationLock(SimTime);
+
+impl AnimationLock {
+    pub fn lock(&mut self, until: SimTime) {
+        self.0 = until;
+    }
+
+    pub fn is_locked(&self, sim_time: SimTime) -> bool {
+        sim_time < self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_locked() {
+        let mut animation_lock = AnimationLock::default();
+        assert!(!animation_lock.is_locked(0));
+        animation_lock.lock(600);
+        assert!(animation_lock.is_locked(0));
+        assert!(animation_lock.is_locked(599));
+        assert!(!animation_lock.is_locked(600));
+    }
+}
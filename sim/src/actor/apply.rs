@@ -1,21 +1,71 @@
 use super::calc;
-use super::status_effect::status::{SetCombo, Status, StatusFlag, StatusFlags};
-use super::status_effect::StatusEffect;
+use super::damage::HitType;
+use super::stat::{Stat, Stats};
+use super::status_effect::status::{DamageTick, SetCombo, Status, StatusFlag, StatusFlags};
+use super::status_effect::{self, StatusEffect};
 use super::{ActiveCombos, QueryActor};
+use crate::combat_log::{CombatEventKind, CombatLog};
 use crate::sim::{SimState, SimTime};
 use bevy_ecs::prelude::Entity;
 use std::sync::Arc;
 
+pub const DEFAULT_GCD: SimTime = 2500;
+
 pub trait Apply {
-    fn apply(&self, sim: &SimState, query: &mut QueryActor, source: Entity, target: Entity);
+    fn apply(
+        &self,
+        sim: &SimState,
+        log: &mut CombatLog,
+        query: &mut QueryActor,
+        source: Entity,
+        target: Entity,
+    );
+
+    // at_cast_start is true for results that apply when a cast starts rather than when it
+    // completes, like recasts.
+    fn at_cast_start(&self) -> bool {
+        false
+    }
+
+    // ticks is true for status effects that act on every server tick, like damage over time.
+    fn ticks(&self) -> bool {
+        false
+    }
+
+    // damage_dealt is the multiplier a status effect puts on damage its holder deals, eg. 1.15
+    // for Lance Charge.
+    fn damage_dealt(&self) -> Option<f64> {
+        None
+    }
+
+    // damage_taken is the multiplier a status effect puts on damage its holder takes.
+    fn damage_taken(&self) -> Option<f64> {
+        None
+    }
+}
+
+// target_stats copies the target's stats, for the bonuses it grants to hits against it.
+pub fn target_stats(query: &mut QueryActor, target: Entity) -> Stats {
+    match query.get_mut(target) {
+        Ok((_, _, _, _, _, _, _, stats, _, _)) => stats.clone(),
+        Err(_) => Stats::default(),
+    }
 }
 
 #[derive(Default)]
 pub struct DoDirectDamage {
+    // action_id is the action the damage is attributed to in the target's Damage breakdown.
+    pub action_id: u32,
     pub potency: i64,
     pub combo_action_id: Option<u32>,
+    // combo_potency replaces potency when the combo is consumed; None keeps potency.
     pub combo_potency: Option<i64>,
     pub attack_type: calc::AttackType,
+    // preserves_combo keeps the source's active combos, eg. for oGCDs.
+    pub preserves_combo: bool,
+    // combo_results are applied after the damage, only when the combo is consumed, eg. the
+    // Disembowel buff.
+    pub combo_results: Vec<Arc<dyn Apply + Send + Sync>>,
 }
 
 impl DoDirectDamage {
@@ -31,30 +81,149 @@ impl DoDirectDamage {
 }
 
 impl Apply for DoDirectDamage {
-    fn apply(&self, sim: &SimState, query: &mut QueryActor, source: Entity, target: Entity) {
+    fn apply(
+        &self,
+        sim: &SimState,
+        log: &mut CombatLog,
+        query: &mut QueryActor,
+        source: Entity,
+        target: Entity,
+    ) {
         let calculated_damage;
-        if let Ok((_, job, _, _, _, _, mut status_effects, stats, mut active_combos)) =
+        let combo_consumed;
+        let potency;
+        let multipliers = status_effect::damage_multipliers(sim, query, source, target);
+        let target_stats = target_stats(query, target);
+        if let Ok((_, job, _, _, _, _, mut status_effects, stats, mut active_combos, _)) =
             query.get_mut(source)
         {
-            let potency = if self.consume_combo(&mut active_combos) {
-                self.combo_potency.expect(
-                    "Consumed a combo, but no combo_potency is set. This should not happen.",
-                )
+            combo_consumed = self.consume_combo(&mut active_combos);
+            potency = if combo_consumed {
+                let action_id = self
+                    .combo_action_id
+                    .expect("Consumed a combo, but no combo_action_id is set.");
+                log.record(
+                    sim.now(),
+                    source,
+                    target,
+                    CombatEventKind::ComboConsumed { action_id },
+                );
+                self.combo_potency.unwrap_or(self.potency)
             } else {
+                if let Some(action_id) = self.combo_action_id {
+                    log.record(
+                        sim.now(),
+                        source,
+                        target,
+                        CombatEventKind::ComboMissed { action_id },
+                    );
+                }
                 self.potency
             };
-            calculated_damage =
-                calc::direct_damage(sim, potency, *job, &stats, self.attack_type, vec![]);
-            status_effects.expire_with_flag(StatusFlag::ExpireOnDirectDamage);
+            calculated_damage = calc::direct_damage(
+                sim,
+                potency,
+                *job,
+                &stats.against(&target_stats),
+                self.attack_type,
+                multipliers.values(),
+            );
+            status_effects.expire_with_flag(
+                StatusFlag::ExpireOnDirectDamage,
+                sim.now(),
+                log,
+                source,
+            );
+            if !self.preserves_combo {
+                status_effects.expire_with_flag(
+                    StatusFlag::ExpireOnComboBreak,
+                    sim.now(),
+                    log,
+                    source,
+                );
+            }
         } else {
             panic!("Tried to get stats of a source with no stats.")
         }
 
-        if let Ok((_, _, _, _, _, mut damage, _, _, _)) = query.get_mut(target) {
-            damage.add(calculated_damage);
+        if let Ok((_, _, _, _, _, mut damage, _, _, _, _)) = query.get_mut(target) {
+            damage.add(
+                source,
+                self.action_id,
+                calculated_damage.amount,
+                HitType::new(calculated_damage.critical_hit, calculated_damage.direct_hit),
+            );
         } else {
             panic!("Tried to do damage to a target that has no Damage component.")
         }
+        log.record(
+            sim.now(),
+            source,
+            target,
+            CombatEventKind::DamageDealt {
+                action_id: self.action_id,
+                potency,
+                amount: calculated_damage.amount,
+                critical_hit: calculated_damage.critical_hit,
+                direct_hit: calculated_damage.direct_hit,
+                multipliers: multipliers.values(),
+                buffs: multipliers.names(),
+            },
+        );
+        if combo_consumed {
+            for result in &self.combo_results {
+                result.apply(sim, log, query, source, target);
+            }
+        }
+    }
+}
+
+/// DoDamageOverTime puts a status on the target that deals `potency` every server tick, from the
+/// source's stats and buffs when it was applied.
+pub struct DoDamageOverTime {
+    // action_id is the action the ticks are attributed to in the target's Damage breakdown.
+    pub action_id: u32,
+    // name is the status the damage over time shows as on the target, eg. Chaos Thrust.
+    pub name: String,
+    pub potency: i64,
+    pub duration: SimTime,
+    pub attack_type: calc::AttackType,
+}
+
+impl Apply for DoDamageOverTime {
+    fn apply(
+        &self,
+        sim: &SimState,
+        log: &mut CombatLog,
+        query: &mut QueryActor,
+        source: Entity,
+        target: Entity,
+    ) {
+        // Buffs and debuffs are snapshotted along with the stats.
+        let multipliers = status_effect::damage_multipliers(sim, query, source, target);
+        let target_stats = target_stats(query, target);
+        let tick = if let Ok((_, job, _, _, _, _, _, stats, _, _)) = query.get_mut(source) {
+            DamageTick {
+                action_id: self.action_id,
+                potency: self.potency,
+                job: *job,
+                stats: stats.against(&target_stats),
+                attack_type: self.attack_type,
+                multipliers,
+            }
+        } else {
+            panic!("Tried to get stats of a source with no stats.")
+        };
+        let give_status_effect = GiveStatusEffect {
+            status: Status {
+                name: self.name.clone(),
+                duration: self.duration,
+                flags: StatusFlags::default(),
+                effects: vec![Arc::new(tick)],
+            },
+            target_source: false,
+        };
+        give_status_effect.apply(sim, log, query, source, target);
     }
 }
 
@@ -64,11 +233,22 @@ pub struct StartRecast {
 }
 
 impl Apply for StartRecast {
-    fn apply(&self, sim: &SimState, query: &mut QueryActor, source: Entity, _target: Entity) {
-        if let Ok((_, _, _, _, mut recast_expirations, _, _, _, _)) = query.get_mut(source) {
+    fn apply(
+        &self,
+        sim: &SimState,
+        _log: &mut CombatLog,
+        query: &mut QueryActor,
+        source: Entity,
+        _target: Entity,
+    ) {
+        if let Ok((_, _, _, _, mut recast_expirations, _, _, _, _, _)) = query.get_mut(source) {
             recast_expirations.set(self.action_id, sim.now() + self.duration);
         }
     }
+
+    fn at_cast_start(&self) -> bool {
+        true
+    }
 }
 
 pub struct GiveStatusEffect {
@@ -77,55 +257,139 @@ pub struct GiveStatusEffect {
 }
 
 impl Apply for GiveStatusEffect {
-    fn apply(&self, sim: &SimState, query: &mut QueryActor, source: Entity, target: Entity) {
+    fn apply(
+        &self,
+        sim: &SimState,
+        log: &mut CombatLog,
+        query: &mut QueryActor,
+        source: Entity,
+        target: Entity,
+    ) {
         let receiver = if self.target_source { source } else { target };
-        if let Ok((_, _, _, _, _, _, mut status_effects, _, _)) = query.get_mut(receiver) {
-            status_effects.add(StatusEffect::new(self.status.clone(), source, sim.now()));
+        if let Ok((_, _, _, _, _, _, mut status_effects, _, _, _)) = query.get_mut(receiver) {
+            let effect = StatusEffect::new(self.status.clone(), source, sim.now());
+            let name = effect.status.name.clone();
+            let expiration = effect.expiration;
+            let kind = if status_effects.add_or_refresh(effect) {
+                CombatEventKind::StatusRefreshed { name, expiration }
+            } else {
+                CombatEventKind::StatusApplied { name, expiration }
+            };
+            log.record(sim.now(), source, receiver, kind);
         }
     }
 }
 
 pub struct StartGcd {
     base_duration: SimTime,
+    // speed is the stat that shortens the GCD: skill speed for weaponskills, spell speed for
+    // spells.
+    speed: Stat,
 }
 
 impl StartGcd {
     pub fn new(duration: SimTime) -> Self {
         StartGcd {
             base_duration: duration,
+            speed: Stat::SkillSpeed,
         }
     }
+
+    pub fn with_speed(mut self, speed: Stat) -> Self {
+        self.speed = speed;
+        self
+    }
 }
 
 impl Default for StartGcd {
     fn default() -> Self {
-        StartGcd::new(2500)
+        StartGcd::new(DEFAULT_GCD)
     }
 }
 
 impl Apply for StartGcd {
-    fn apply(&self, sim: &SimState, query: &mut QueryActor, source: Entity, _target: Entity) {
-        if let Ok((_, _, _, _, mut recast_expirations, _, _, _, _)) = query.get_mut(source) {
-            // TODO: calculate duration with skill speed
-            recast_expirations.set_gcd(sim.now() + self.base_duration);
+    fn apply(
+        &self,
+        sim: &SimState,
+        log: &mut CombatLog,
+        query: &mut QueryActor,
+        source: Entity,
+        _target: Entity,
+    ) {
+        if let Ok((_, _, _, _, mut recast_expirations, _, _, stats, _, _)) = query.get_mut(source) {
+            let duration = calc::gcd(
+                self.base_duration,
+                stats.get(self.speed),
+                stats.get(Stat::Haste),
+            );
+            recast_expirations.set_gcd(sim.now() + duration);
+            log.record(
+                sim.now(),
+                source,
+                source,
+                CombatEventKind::GcdStarted { duration },
+            );
         }
     }
+
+    fn at_cast_start(&self) -> bool {
+        true
+    }
 }
 
 pub struct ApplyCombo(pub u32);
 
 impl Apply for ApplyCombo {
-    fn apply(&self, sim: &SimState, query: &mut QueryActor, source: Entity, _target: Entity) {
+    fn apply(
+        &self,
+        sim: &SimState,
+        log: &mut CombatLog,
+        query: &mut QueryActor,
+        source: Entity,
+        _target: Entity,
+    ) {
         let set_combo = Status {
             name: format!("{} Combo", self.0),
             // TODO: figure out how long combos actually last.
             duration: 15000,
-            flags: StatusFlags::new(&[StatusFlag::ExpireOnDirectDamage]),
+            flags: StatusFlags::new(&[StatusFlag::ExpireOnComboBreak]),
             effects: vec![Arc::new(SetCombo(self.0))],
-            ..Default::default()
         };
-        if let Ok((_, _, _, _, _, _, mut status_effects, _, _)) = query.get_mut(source) {
-            status_effects.add(StatusEffect::new(set_combo, source, sim.now()));
+        if let Ok((_, _, _, _, _, _, mut status_effects, _, _, _)) = query.get_mut(source) {
+            status_effects.add_or_refresh(StatusEffect::new(set_combo, source, sim.now()));
+            log.record(
+                sim.now(),
+                source,
+                source,
+                CombatEventKind::ComboSet { action_id: self.0 },
+            );
+        }
+    }
+}
+
+/// ModifyGauge adds `amount` to a resource on the source's job gauge, eg. a cartridge for Solid
+/// Barrel or -1 to spend one on Burst Strike. The resource never goes below 0, or above `max`.
+pub struct ModifyGauge {
+    pub name: String,
+    pub amount: i64,
+    pub max: Option<i64>,
+}
+
+impl Apply for ModifyGauge {
+    fn apply(
+        &self,
+        _sim: &SimState,
+        _log: &mut CombatLog,
+        query: &mut QueryActor,
+        source: Entity,
+        _target: Entity,
+    ) {
+        if let Ok((_, _, _, _, _, _, _, _, _, mut job_gauge)) = query.get_mut(source) {
+            let mut amount = (job_gauge.get(&self.name) + self.amount).max(0);
+            if let Some(max) = self.max {
+                amount = amount.min(max);
+            }
+            job_gauge.set(&self.name, amount);
         }
     }
 }
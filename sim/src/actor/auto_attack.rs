@@ -0,0 +1,37 @@
+use crate::sim::SimTime;
+
+// AUTO_ATTACK_ID is the action id auto-attack damage is recorded under, the game's own "Attack".
+pub const AUTO_ATTACK_ID: u32 = 7;
+pub const AUTO_ATTACK_NAME: &str = "Attack";
+
+/// AutoAttack is when an actor's next auto-attack is due. Actors start swinging at the pull.
+#[derive(Debug, Default)]
+pub struct AutoAttack(SimTime);
+
+impl AutoAttack {
+    pub fn is_due(&self, sim_time: SimTime) -> bool {
+        sim_time >= self.0
+    }
+
+    // swing starts the delay until the next auto-attack, and returns when it is due.
+    pub fn swing(&mut self, sim_time: SimTime, delay: SimTime) -> SimTime {
+        self.0 = sim_time + delay;
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swing() {
+        let mut auto_attack = AutoAttack::default();
+        assert!(auto_attack.is_due(0));
+        assert_eq!(2800, auto_attack.swing(0, 2800));
+        assert!(!auto_attack.is_due(2799));
+        assert!(auto_attack.is_due(2800));
+        // A swing held back, eg. by a cast, waits the full delay from when it lands.
+        assert_eq!(6000, auto_attack.swing(3200, 2800));
+    }
+}
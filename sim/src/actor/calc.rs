@@ -1,21 +1,46 @@
 pub mod lookup;
 use super::stat::{SpecialStat, Stat, Stats};
-use crate::sim::SimState;
+use crate::sim::{DamageMode, SimState, SimTime};
 use math::round::floor;
+use serde::Deserialize;
 
 #[allow(dead_code)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum AttackType {
+    #[default]
     PHYSICAL,
     MAGIC,
 }
 
-impl Default for AttackType {
-    fn default() -> Self {
-        AttackType::PHYSICAL
+impl AttackType {
+    // weapon_damage is the weapon damage stat the attack scales with.
+    fn weapon_damage(&self, stats: &Stats) -> i64 {
+        match self {
+            AttackType::PHYSICAL => stats.get(Stat::PhysicalWeaponDamage),
+            AttackType::MAGIC => stats.get(Stat::MagicWeaponDamage),
+        }
+    }
+
+    // speed is the speed stat damage over time of this type scales with.
+    fn speed(&self) -> Stat {
+        match self {
+            AttackType::PHYSICAL => Stat::SkillSpeed,
+            AttackType::MAGIC => Stat::SpellSpeed,
+        }
     }
 }
 
+/// DirectDamage is the outcome of a single direct damage calculation, or a tick of damage over
+/// time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DirectDamage {
+    pub amount: i64,
+    // critical_hit and direct_hit are always false for expected damage, since nothing is rolled.
+    pub critical_hit: bool,
+    pub direct_hit: bool,
+}
+
 /// https://www.akhmorning.com/allagan-studies/how-to-be-a-math-wizard/shadowbringers/damage-and-healing/#direct-damage-d
 pub fn direct_damage(
     sim: &SimState,
@@ -24,37 +49,217 @@ pub fn direct_damage(
     stats: &Stats,
     attack_type: AttackType,
     multipliers: Vec<f64>,
-) -> i64 {
-    let fatk = attack_power(job, stats.get(Stat::AttackPower));
-    let fdet = determination(stats.get(Stat::Determination));
-    // https://www.akhmorning.com/allagan-studies/how-to-be-a-math-wizard/shadowbringers/damage-and-healing/#direct-damage-d
-    // D1 = ⌊ Potency × f(ATK) × f(DET) ⌋ /100 ⌋ /1000 ⌋
-    let d1 = ((potency * fatk * fdet) / 100) / 1000;
+) -> DirectDamage {
+    let d1 = potency_damage(potency, job, stats);
 
     let ftnc = tenacity(stats.get(Stat::Tenacity));
-    let wd = match attack_type {
-        AttackType::PHYSICAL => stats.get(Stat::PhysicalWeaponDamage),
-        AttackType::MAGIC => stats.get(Stat::MagicWeaponDamage),
-    };
-    let fwd = weapon_damage(job, wd);
+    let fwd = weapon_damage(job, attack_type.weapon_damage(stats));
     // D2 = ⌊ D1 × f(TNC) ⌋ /1000 ⌋ × f(WD) ⌋ /100 ⌋ × Trait ⌋ /100 ⌋
     let d2 = (((((d1 * ftnc) / 1000) * fwd) / 100) * job.trait_multiplier()) / 100;
 
-    let crit = critical_hit(
+    let crit_percent_override = stats.get_special(SpecialStat::CriticalHitPercentOverride);
+    let dh_percent_override = stats.get_special(SpecialStat::DirectHitPercentOverride);
+    rolled_direct_damage(
         sim,
-        stats.get(Stat::CriticalHitRate),
-        stats.get_special(SpecialStat::CriticalHitPercentOverride),
+        d2,
+        stats,
+        crit_percent_override,
+        dh_percent_override,
+        multipliers,
+    )
+}
+
+/// A single auto-attack. Auto-attacks scale with speed and weapon delay instead of f(WD), and
+/// ignore crit and direct hit overrides, which only apply to actions.
+/// https://www.akhmorning.com/allagan-studies/how-to-be-a-math-wizard/shadowbringers/damage-and-healing/#auto-attacks
+pub fn auto_attack(
+    sim: &SimState,
+    potency: i64,
+    job: lookup::Job,
+    stats: &Stats,
+    multipliers: Vec<f64>,
+) -> DirectDamage {
+    let d1 = potency_damage(potency, job, stats);
+
+    let ftnc = tenacity(stats.get(Stat::Tenacity));
+    let fspd = speed_modifier(stats.get(Stat::SkillSpeed));
+    let fauto = auto_attack_modifier(
+        job,
+        stats.get(Stat::PhysicalWeaponDamage),
+        stats.get(Stat::WeaponDelay),
     );
-    let dh = direct_hit(sim, stats.get(Stat::DirectHitRate));
-    // D3 = ⌊ D2 × CRIT? ⌋ /1000 ⌋ × DH? ⌋ /100 ⌋
-    let d3 = (((d2 * crit) / 1000) * dh) / 100;
-    // D = ⌊ D3 × rand[95,105] ⌋ /100 ⌋
-    let d = d3 * sim.rng.random_from_range(95, 106) / 100;
+    // D2 = ⌊ D1 × f(TNC) ⌋ /1000 ⌋ × f(SPD) ⌋ /1000 ⌋ × f(AUTO) ⌋ /100 ⌋ × Trait ⌋ /100 ⌋
+    let d2 =
+        (((((((d1 * ftnc) / 1000) * fspd) / 1000) * fauto) / 100) * job.trait_multiplier()) / 100;
+
+    rolled_direct_damage(sim, d2, stats, None, None, multipliers)
+}
 
+// rolled_direct_damage rolls crit, direct hit and variance on D2 of a direct hit, then applies
+// multipliers.
+fn rolled_direct_damage(
+    sim: &SimState,
+    d2: i64,
+    stats: &Stats,
+    crit_percent_override: Option<&i64>,
+    dh_percent_override: Option<&i64>,
+    multipliers: Vec<f64>,
+) -> DirectDamage {
+    let chr = stats.get(Stat::CriticalHitRate);
+    let dhr = stats.get(Stat::DirectHitRate);
+    let crit_bonus = special_bonus(stats, SpecialStat::CriticalHitPercentBonus);
+    let dh_bonus = special_bonus(stats, SpecialStat::DirectHitPercentBonus);
+    let guaranteed_crit =
+        guaranteed_critical_hit_bonus(sim, chr, crit_percent_override, crit_bonus);
+    let guaranteed_dh = guaranteed_direct_hit_bonus(sim, dh_percent_override, dh_bonus);
+    match sim.damage_mode() {
+        DamageMode::Random => {
+            let critical_hit = is_crit(sim, chr, crit_percent_override, crit_bonus);
+            let direct_hit = is_direct(sim, dhr, dh_percent_override, dh_bonus);
+            let crit = critical_hit_multiplier(chr, critical_hit);
+            let dh = direct_hit_multiplier(direct_hit);
+            // D3 = ⌊ D2 × CRIT? ⌋ /1000 ⌋ × DH? ⌋ /100 ⌋
+            let d3 = (((d2 * crit) / 1000) * dh) / 100;
+            // ⌊ ⌊ D3 × guaranteed CRIT bonus ⌋ /1000 ⌋ × guaranteed DH bonus ⌋ /1000 ⌋
+            let d3 = (((d3 * guaranteed_crit) / 1000) * guaranteed_dh) / 1000;
+            // D = ⌊ D3 × rand[95,105] ⌋ /100 ⌋
+            let d = d3 * sim.rng.random_from_range(95, 106) / 100;
+
+            DirectDamage {
+                amount: buffed(d, &multipliers),
+                critical_hit,
+                direct_hit,
+            }
+        }
+        DamageMode::Expected => {
+            // E[D] = D2 × E[CRIT] /1000 × E[DH] /100, and E[rand[95,105]] /100 = 1.
+            // Nothing is floored since the result is an average over every possible roll.
+            let d = d2 as f64 * expected_critical_hit(chr, crit_percent_override, crit_bonus)
+                / 1000.0
+                * expected_direct_hit(dhr, dh_percent_override, dh_bonus)
+                / 100.0
+                * guaranteed_crit as f64
+                / 1000.0
+                * guaranteed_dh as f64
+                / 1000.0;
+            DirectDamage {
+                amount: expected_buffed(d, &multipliers),
+                critical_hit: false,
+                direct_hit: false,
+            }
+        }
+    }
+}
+
+/// A single tick of damage over time. Ticks roll their own crit and direct hit with any rate
+/// bonuses, but ignore crit and direct hit overrides, which only apply to direct damage.
+/// https://www.akhmorning.com/allagan-studies/how-to-be-a-math-wizard/shadowbringers/damage-and-healing/#damage-over-time
+pub fn damage_over_time(
+    sim: &SimState,
+    potency: i64,
+    job: lookup::Job,
+    stats: &Stats,
+    attack_type: AttackType,
+    multipliers: Vec<f64>,
+) -> DirectDamage {
+    let d1 = potency_damage(potency, job, stats);
+
+    let ftnc = tenacity(stats.get(Stat::Tenacity));
+    let fspd = speed_modifier(stats.get(attack_type.speed()));
+    let fwd = weapon_damage(job, attack_type.weapon_damage(stats));
+    // D2 = ⌊ D1 × f(TNC) ⌋ /1000 ⌋ × f(SPD) ⌋ /1000 ⌋ × f(WD) ⌋ /100 ⌋ × Trait ⌋ /100 ⌋ + 1
+    let d2 =
+        (((((((d1 * ftnc) / 1000) * fspd) / 1000) * fwd) / 100) * job.trait_multiplier()) / 100 + 1;
+
+    let chr = stats.get(Stat::CriticalHitRate);
+    let dhr = stats.get(Stat::DirectHitRate);
+    let crit_bonus = special_bonus(stats, SpecialStat::CriticalHitPercentBonus);
+    let dh_bonus = special_bonus(stats, SpecialStat::DirectHitPercentBonus);
+    match sim.damage_mode() {
+        DamageMode::Random => {
+            // D3 = ⌊ D2 × rand[95,105] ⌋ /100 ⌋
+            let d3 = d2 * sim.rng.random_from_range(95, 106) / 100;
+            let critical_hit = is_crit(sim, chr, None, crit_bonus);
+            let direct_hit = is_direct(sim, dhr, None, dh_bonus);
+            let crit = critical_hit_multiplier(chr, critical_hit);
+            let dh = direct_hit_multiplier(direct_hit);
+            // D = ⌊ D3 × CRIT? ⌋ /1000 ⌋ × DH? ⌋ /100 ⌋
+            let d = (((d3 * crit) / 1000) * dh) / 100;
+            DirectDamage {
+                amount: buffed(d, &multipliers),
+                critical_hit,
+                direct_hit,
+            }
+        }
+        DamageMode::Expected => {
+            let d = d2 as f64 * expected_critical_hit(chr, None, crit_bonus) / 1000.0
+                * expected_direct_hit(dhr, None, dh_bonus)
+                / 100.0;
+            DirectDamage {
+                amount: expected_buffed(d, &multipliers),
+                critical_hit: false,
+                direct_hit: false,
+            }
+        }
+    }
+}
+
+// special_bonus is a rate bonus from the special stats, or 0 without one.
+fn special_bonus(stats: &Stats, stat: SpecialStat) -> i64 {
+    stats.get_special(stat).map_or(0, |bonus| *bonus)
+}
+
+// potency_damage is D1, the damage of a potency before any other modifiers.
+fn potency_damage(potency: i64, job: lookup::Job, stats: &Stats) -> i64 {
+    let fatk = attack_power(job, stats.get(Stat::AttackPower));
+    let fdet = determination(stats.get(Stat::Determination));
+    // D1 = ⌊ Potency × f(ATK) × f(DET) ⌋ /100 ⌋ /1000 ⌋
+    ((potency * fatk * fdet) / 100) / 1000
+}
+
+// buffed applies multipliers to rolled damage, flooring after each one. Multipliers are whole
+// percents in game, so they're applied in integer math to keep float error out of the floors,
+// eg. 100 × 1.15 would otherwise floor to 114.
+fn buffed(d: i64, multipliers: &[f64]) -> i64 {
     // ⌊ ⌊ D × buff_1 ⌋ × buff_2 ⌋
+    multipliers.iter().fold(d, |total, multiplier| {
+        total * (multiplier * 1000.0).round() as i64 / 1000
+    })
+}
+
+// expected_buffed applies multipliers to expected damage, which is only rounded at the end.
+fn expected_buffed(d: f64, multipliers: &[f64]) -> i64 {
     multipliers
         .iter()
-        .fold(d as f64, |total, multiplier| floor(total * *multiplier, 0)) as i64
+        .fold(d, |total, multiplier| total * *multiplier)
+        .round() as i64
+}
+
+/// GCD is the recast of an action with the given base recast, shortened by skill or spell speed
+/// and haste.
+/// https://www.akhmorning.com/allagan-studies/how-to-be-a-math-wizard/shadowbringers/speed/#gcd-calculation
+pub fn gcd(base: SimTime, speed: i64, haste: i64) -> SimTime {
+    // GCD1 = ⌊ ( 2000 - f(SPD) ) × Base / 1000 ⌋
+    let gcd1 = (2000 - speed_modifier(speed)) * base as i64 / 1000;
+    // GCD = ⌊ GCD1 × ( 100 - Haste ) / 1000 ⌋ in centiseconds
+    let gcd = gcd1 * (100 - haste) / 1000;
+    (gcd * 10).max(0) as SimTime
+}
+
+/// Auto-attack delay is the weapon's delay, shortened by haste but not by speed.
+pub fn auto_attack_delay(delay: SimTime, haste: i64) -> SimTime {
+    (delay as i64 * (100 - haste) / 100).max(0) as SimTime
+}
+
+/// F(SPD)
+/// https://www.akhmorning.com/allagan-studies/how-to-be-a-math-wizard/shadowbringers/functions/#speed-fspd
+fn speed_modifier(speed: i64) -> i64 {
+    // Substats never go below the level's base, so an actor without speed gets the base GCD.
+    let speed = speed.max(lookup::level_modifiers(lookup::LevelColumn::SUB));
+    // ⌊ 130 · ( SPD - LevelModLv, SUB )/ LevelModLv, DIV + 1000 ⌋
+    130 * (speed - lookup::level_modifiers(lookup::LevelColumn::SUB))
+        / lookup::level_modifiers(lookup::LevelColumn::DIV)
+        + 1000
 }
 
 /// Level 80 F(AP)
@@ -99,6 +304,14 @@ fn weapon_damage(job: lookup::Job, wd: i64) -> i64 {
         + wd
 }
 
+/// F(AUTO)
+/// https://www.akhmorning.com/allagan-studies/how-to-be-a-math-wizard/shadowbringers/functions/#auto-attack-fauto
+fn auto_attack_modifier(job: lookup::Job, wd: i64, delay: i64) -> i64 {
+    // ⌊ ( ⌊ LevelModLv, MAIN · JobModJob, Attribute / 1000 ⌋ + WD ) · ( Delay / 3 ) ⌋, with the
+    // delay in milliseconds.
+    weapon_damage(job, wd) * delay / 3000
+}
+
 /// P(CHR)
 /// https://www.akhmorning.com/allagan-studies/how-to-be-a-math-wizard/shadowbringers/parameters/#critical-hit-probability
 fn critical_hit_rate(chr: i64) -> f64 {
@@ -111,13 +324,18 @@ fn critical_hit_rate(chr: i64) -> f64 {
     ) / 10.0
 }
 
-fn is_crit(sim: &SimState, chr: i64, crit_percent_override: Option<&i64>) -> bool {
-    let roll = sim.rng.random();
-    let percent = match crit_percent_override {
+// critical_hit_percent is the chance to crit with any bonuses, such as Battle Litany, unless an
+// override such as Life Surge sets it.
+fn critical_hit_percent(chr: i64, crit_percent_override: Option<&i64>, crit_bonus: i64) -> f64 {
+    match crit_percent_override {
         Some(p) => *p as f64,
-        None => critical_hit_rate(chr),
-    };
-    roll < percent / 100.0
+        None => critical_hit_rate(chr) + crit_bonus as f64,
+    }
+}
+
+fn is_crit(sim: &SimState, chr: i64, crit_percent_override: Option<&i64>, crit_bonus: i64) -> bool {
+    let roll = sim.rng.random();
+    roll < critical_hit_percent(chr, crit_percent_override, crit_bonus) / 100.0
 }
 
 fn critical_hit_damage(crit: i64) -> i64 {
@@ -129,13 +347,54 @@ fn critical_hit_damage(crit: i64) -> i64 {
 
 /// F(CRIT)
 /// https://www.akhmorning.com/allagan-studies/how-to-be-a-math-wizard/shadowbringers/functions/#critical-hit-damage-fcrit
-fn critical_hit(sim: &SimState, crit: i64, crit_percent_override: Option<&i64>) -> i64 {
-    if !is_crit(sim, crit, crit_percent_override) {
+fn critical_hit_multiplier(crit: i64, is_crit: bool) -> i64 {
+    if !is_crit {
         return 1000;
     }
     critical_hit_damage(crit)
 }
 
+/// E[F(CRIT)], the crit damage multiplier weighted by the chance to crit.
+fn expected_critical_hit(crit: i64, crit_percent_override: Option<&i64>, crit_bonus: i64) -> f64 {
+    let probability =
+        (critical_hit_percent(crit, crit_percent_override, crit_bonus) / 100.0).min(1.0);
+    1000.0 + probability * (critical_hit_damage(crit) - 1000) as f64
+}
+
+// is_guaranteed is true for an override that always hits, eg. Life Surge.
+fn is_guaranteed(percent_override: Option<&i64>) -> bool {
+    percent_override.is_some_and(|p| *p >= 100)
+}
+
+/// Under the Endwalker rules, crit rate bonuses on a guaranteed crit raise its damage instead, by
+/// the crit damage they would have added on average. In thousandths, 1000 without a bonus.
+fn guaranteed_critical_hit_bonus(
+    sim: &SimState,
+    chr: i64,
+    crit_percent_override: Option<&i64>,
+    crit_bonus: i64,
+) -> i64 {
+    if !sim.ruleset().converts_guaranteed_hit_bonuses() || !is_guaranteed(crit_percent_override) {
+        return 1000;
+    }
+    // ⌊ 1000 + ( f(CRIT) - 1000 ) × bonus /100 ⌋
+    1000 + (critical_hit_damage(chr) - 1000) * crit_bonus / 100
+}
+
+/// guaranteed_direct_hit_bonus is guaranteed_critical_hit_bonus for direct hits, which always add
+/// 25% damage.
+fn guaranteed_direct_hit_bonus(
+    sim: &SimState,
+    dh_percent_override: Option<&i64>,
+    dh_bonus: i64,
+) -> i64 {
+    if !sim.ruleset().converts_guaranteed_hit_bonuses() || !is_guaranteed(dh_percent_override) {
+        return 1000;
+    }
+    // ⌊ 1000 + 250 × bonus /100 ⌋
+    1000 + 250 * dh_bonus / 100
+}
+
 /// P(DHR)
 /// https://www.akhmorning.com/allagan-studies/how-to-be-a-math-wizard/shadowbringers/parameters/#pdhr
 fn direct_hit_rate(dhr: i64) -> f64 {
@@ -147,24 +406,39 @@ fn direct_hit_rate(dhr: i64) -> f64 {
     ) / 10.0
 }
 
-fn is_direct(sim: &SimState, dhr: i64) -> bool {
+// direct_hit_percent is the chance to direct hit with any bonuses, such as Battle Voice, unless
+// an override sets it.
+fn direct_hit_percent(dhr: i64, dh_percent_override: Option<&i64>, dh_bonus: i64) -> f64 {
+    match dh_percent_override {
+        Some(p) => *p as f64,
+        None => direct_hit_rate(dhr) + dh_bonus as f64,
+    }
+}
+
+fn is_direct(sim: &SimState, dhr: i64, dh_percent_override: Option<&i64>, dh_bonus: i64) -> bool {
     let roll = sim.rng.random();
-    let probability = direct_hit_rate(dhr) / 100.0;
+    let probability = direct_hit_percent(dhr, dh_percent_override, dh_bonus) / 100.0;
     roll < probability
 }
 
-fn direct_hit(sim: &SimState, crit: i64) -> i64 {
-    if is_direct(sim, crit) {
+fn direct_hit_multiplier(is_direct: bool) -> i64 {
+    if is_direct {
         125
     } else {
         100
     }
 }
 
+/// E[DH], the direct hit multiplier weighted by the chance to direct hit.
+fn expected_direct_hit(dhr: i64, dh_percent_override: Option<&i64>, dh_bonus: i64) -> f64 {
+    let probability = (direct_hit_percent(dhr, dh_percent_override, dh_bonus) / 100.0).min(1.0);
+    100.0 + probability * 25.0
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::sim::SimRng;
+    use crate::sim::{Ruleset, SimRng};
     pub struct FakeRng {
         random_value: f64,
         random_from_range_value: i64,
@@ -207,6 +481,46 @@ mod test {
         assert_eq!(1121, tenacity(4373));
     }
 
+    #[test]
+    fn test_speed_modifier() {
+        assert_eq!(1000, speed_modifier(380));
+        assert_eq!(1000, speed_modifier(0));
+        assert_eq!(1024, speed_modifier(1012));
+    }
+
+    #[test]
+    fn test_gcd() {
+        // Each speed tier shortens a 2.5s GCD by 10ms.
+        assert_eq!(2500, gcd(2500, 380, 0));
+        assert_eq!(2500, gcd(2500, 405, 0));
+        assert_eq!(2490, gcd(2500, 406, 0));
+        assert_eq!(2440, gcd(2500, 1012, 0));
+        assert_eq!(2410, gcd(2500, 1319, 0));
+        assert_eq!(2400, gcd(2500, 1320, 0));
+        assert_eq!(1500, gcd(1500, 380, 0));
+        assert_eq!(2800, gcd(2800, 380, 0));
+    }
+
+    #[test]
+    fn test_gcd_haste() {
+        assert_eq!(2000, gcd(2500, 380, 20));
+        assert_eq!(2120, gcd(2500, 380, 15));
+        assert_eq!(1950, gcd(2500, 1012, 20));
+    }
+
+    #[test]
+    fn test_auto_attack_delay() {
+        assert_eq!(2800, auto_attack_delay(2800, 0));
+        assert_eq!(2240, auto_attack_delay(2800, 20));
+    }
+
+    #[test]
+    fn test_auto_attack_modifier() {
+        // ⌊ 168 × 2.24 / 3 ⌋
+        assert_eq!(125, auto_attack_modifier(lookup::Job::PLD, 134, 2240));
+        assert_eq!(0, auto_attack_modifier(lookup::Job::PLD, 134, 0));
+    }
+
     #[test]
     fn test_critical_hit_damage() {
         assert_eq!(1642, critical_hit_damage(4373));
@@ -221,7 +535,7 @@ mod test {
             random_from_range_value: 100,
         });
 
-        assert_eq!(false, is_crit(&sim, 0, None));
+        assert_eq!(false, is_crit(&sim, 0, None, 0));
     }
 
     #[test]
@@ -231,7 +545,33 @@ mod test {
             random_from_range_value: 100,
         });
 
-        assert_eq!(true, is_crit(&sim, 0, Some::<&i64>(&51)));
+        assert_eq!(true, is_crit(&sim, 0, Some::<&i64>(&51), 0));
+    }
+
+    #[test]
+    fn test_is_crit_with_bonus() {
+        let sim = SimState::new(FakeRng {
+            random_value: 0.5,
+            random_from_range_value: 100,
+        });
+
+        // 5% base, so it takes more than 45% from buffs to beat the roll.
+        assert!(!is_crit(&sim, 380, None, 45));
+        assert!(is_crit(&sim, 380, None, 46));
+        // An override ignores bonuses.
+        assert!(!is_crit(&sim, 380, Some(&0), 100));
+    }
+
+    #[test]
+    fn test_is_direct() {
+        let sim = SimState::new(FakeRng {
+            random_value: 0.5,
+            random_from_range_value: 100,
+        });
+
+        assert!(!is_direct(&sim, 380, None, 50));
+        assert!(is_direct(&sim, 380, None, 51));
+        assert!(is_direct(&sim, 380, Some(&100), 0));
     }
 
     fn get_stats() -> Stats {
@@ -269,8 +609,263 @@ mod test {
         let attack_type = AttackType::PHYSICAL;
 
         assert_eq!(
-            6795,
+            DirectDamage {
+                amount: 6795,
+                critical_hit: false,
+                direct_hit: false,
+            },
             direct_damage(&sim, potency, job, &stats, attack_type, vec![])
         );
     }
+
+    #[test]
+    fn test_crit_direct_hit_damage() {
+        let sim = SimState::new(FakeRng {
+            random_value: 0.0,
+            random_from_range_value: 100,
+        });
+        let stats = get_stats();
+
+        // ⌊ ⌊ 6795 × 1591 /1000 ⌋ × 125 /100 ⌋
+        assert_eq!(
+            DirectDamage {
+                amount: 13512,
+                critical_hit: true,
+                direct_hit: true,
+            },
+            direct_damage(
+                &sim,
+                200,
+                lookup::Job::PLD,
+                &stats,
+                AttackType::PHYSICAL,
+                vec![]
+            )
+        );
+    }
+
+    #[test]
+    fn test_damage_over_time() {
+        let sim = SimState::new(FakeRng {
+            random_value: 1.0,
+            random_from_range_value: 100,
+        });
+        let stats = get_stats();
+
+        // Same D1 as direct damage, but ⌊ ⌊ 4045 × f(SPD) 1024 /1000 ⌋ × 168 /100 ⌋ + 1
+        assert_eq!(
+            DirectDamage {
+                amount: 6959,
+                critical_hit: false,
+                direct_hit: false,
+            },
+            damage_over_time(
+                &sim,
+                200,
+                lookup::Job::PLD,
+                &stats,
+                AttackType::PHYSICAL,
+                vec![]
+            )
+        );
+    }
+
+    #[test]
+    fn test_damage_over_time_crit_direct_hit() {
+        let sim = SimState::new(FakeRng {
+            random_value: 0.0,
+            random_from_range_value: 100,
+        });
+        let mut stats = get_stats();
+        // Crit overrides don't apply to ticks, so this is still a 1.591x crit.
+        stats.set_special(SpecialStat::CriticalHitPercentOverride, 0);
+
+        // ⌊ ⌊ 6959 × 1591 /1000 ⌋ × 125 /100 ⌋
+        assert_eq!(
+            DirectDamage {
+                amount: 13838,
+                critical_hit: true,
+                direct_hit: true,
+            },
+            damage_over_time(
+                &sim,
+                200,
+                lookup::Job::PLD,
+                &stats,
+                AttackType::PHYSICAL,
+                vec![]
+            )
+        );
+
+        let sim = sim.with_damage_mode(DamageMode::Expected);
+        // 6959 × 1.142431 × 1.0515
+        assert_eq!(
+            8360,
+            damage_over_time(
+                &sim,
+                200,
+                lookup::Job::PLD,
+                &stats,
+                AttackType::PHYSICAL,
+                vec![]
+            )
+            .amount
+        );
+    }
+
+    #[test]
+    fn test_auto_attack() {
+        let sim = SimState::new(FakeRng {
+            random_value: 1.0,
+            random_from_range_value: 100,
+        });
+        let mut stats = get_stats();
+        stats.set_base(Stat::WeaponDelay, 2240);
+        // Crit overrides don't apply to auto-attacks.
+        stats.set_special(SpecialStat::CriticalHitPercentOverride, 100);
+
+        // D1 2211, ⌊ ⌊ ⌊ 2211 × 1006 /1000 ⌋ × f(SPD) 1024 /1000 ⌋ × f(AUTO) 125 /100 ⌋
+        assert_eq!(
+            DirectDamage {
+                amount: 2846,
+                critical_hit: false,
+                direct_hit: false,
+            },
+            auto_attack(&sim, 110, lookup::Job::PLD, &stats, vec![])
+        );
+    }
+
+    #[test]
+    fn test_buffed() {
+        assert_eq!(115, buffed(100, &[1.15]));
+        // ⌊ ⌊ 6795 × 1.15 ⌋ × 1.1 ⌋
+        assert_eq!(8595, buffed(6795, &[1.15, 1.1]));
+        assert_eq!(6795, buffed(6795, &[]));
+    }
+
+    #[test]
+    fn test_guaranteed_hit_bonuses() {
+        // Life Surge with Battle Litany.
+        let sim = SimState::new(FakeRng {
+            random_value: 0.99,
+            random_from_range_value: 100,
+        });
+        let mut stats = get_stats();
+        stats.set_special(SpecialStat::CriticalHitPercentOverride, 100);
+        stats.add_special(SpecialStat::CriticalHitPercentBonus, 10);
+        let life_surge = |sim: &SimState| {
+            direct_damage(
+                sim,
+                200,
+                lookup::Job::PLD,
+                &stats,
+                AttackType::PHYSICAL,
+                vec![],
+            )
+        };
+
+        // ⌊ 6795 × 1591 /1000 ⌋, the bonus is wasted.
+        assert_eq!(
+            DirectDamage {
+                amount: 10810,
+                critical_hit: true,
+                direct_hit: false,
+            },
+            life_surge(&sim)
+        );
+
+        let sim = sim.with_ruleset(Ruleset::Endwalker);
+        // ⌊ 10810 × ( 1000 + 591 × 10 /100 ) /1000 ⌋
+        assert_eq!(
+            DirectDamage {
+                amount: 11447,
+                critical_hit: true,
+                direct_hit: false,
+            },
+            life_surge(&sim)
+        );
+
+        let sim = sim.with_damage_mode(DamageMode::Expected);
+        // 6795 × 1.591 × 1.0515 × 1.059
+        assert_eq!(12038, life_surge(&sim).amount);
+    }
+
+    #[test]
+    fn test_guaranteed_direct_hit_bonus() {
+        let sim = SimState::new(FakeRng {
+            random_value: 0.99,
+            random_from_range_value: 100,
+        })
+        .with_ruleset(Ruleset::Endwalker);
+        assert_eq!(1000, guaranteed_direct_hit_bonus(&sim, None, 20));
+        assert_eq!(1000, guaranteed_direct_hit_bonus(&sim, Some(&100), 0));
+        assert_eq!(1050, guaranteed_direct_hit_bonus(&sim, Some(&100), 20));
+        let sim = sim.with_ruleset(Ruleset::Shadowbringers);
+        assert_eq!(1000, guaranteed_direct_hit_bonus(&sim, Some(&100), 20));
+    }
+
+    #[test]
+    fn test_expected_critical_hit() {
+        assert_eq!(1000.0, expected_critical_hit(380, Some(&0), 0));
+        assert_eq!(1591.0, expected_critical_hit(3543, Some(&100), 0));
+        // 24.1% chance of a 1.591x hit.
+        assert_eq!(
+            1142.431,
+            (expected_critical_hit(3543, None, 0) * 1000.0).round() / 1000.0
+        );
+    }
+
+    #[test]
+    fn test_expected_critical_hit_with_bonus() {
+        // 34.1% chance of a 1.591x hit.
+        assert_eq!(
+            1201.531,
+            (expected_critical_hit(3543, None, 10) * 1000.0).round() / 1000.0
+        );
+        assert_eq!(1591.0, expected_critical_hit(3543, None, 100));
+    }
+
+    #[test]
+    fn test_expected_direct_hit() {
+        assert_eq!(100.0, expected_direct_hit(380, None, 0));
+        assert_eq!(105.15, expected_direct_hit(1620, None, 0));
+        assert_eq!(107.65, expected_direct_hit(1620, None, 10));
+        assert_eq!(125.0, expected_direct_hit(1620, Some(&100), 0));
+    }
+
+    #[test]
+    fn test_expected_damage_mode() {
+        let sim = SimState::new(FakeRng {
+            random_value: 1.0,
+            random_from_range_value: 100,
+        })
+        .with_damage_mode(DamageMode::Expected);
+        let stats = get_stats();
+
+        // 6795 × 1.142431 × 1.0515
+        assert_eq!(
+            8163,
+            direct_damage(
+                &sim,
+                200,
+                lookup::Job::PLD,
+                &stats,
+                AttackType::PHYSICAL,
+                vec![]
+            )
+            .amount
+        );
+        assert_eq!(
+            8979,
+            direct_damage(
+                &sim,
+                200,
+                lookup::Job::PLD,
+                &stats,
+                AttackType::PHYSICAL,
+                vec![1.1]
+            )
+            .amount
+        );
+    }
 }
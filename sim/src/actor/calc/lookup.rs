@@ -59,6 +59,49 @@ pub enum Job {
     None,
 }
 
+// JOBS lists every playable job and class, in the order they are declared.
+pub const JOBS: [Job; 27] = [
+    Job::GLA,
+    Job::PGL,
+    Job::MRD,
+    Job::LNC,
+    Job::ARC,
+    Job::CNJ,
+    Job::THM,
+    Job::PLD,
+    Job::MNK,
+    Job::WAR,
+    Job::DRG,
+    Job::BRD,
+    Job::WHM,
+    Job::BLM,
+    Job::ACN,
+    Job::SMN,
+    Job::SCH,
+    Job::ROG,
+    Job::NIN,
+    Job::MCH,
+    Job::DRK,
+    Job::AST,
+    Job::SAM,
+    Job::RDM,
+    Job::BLU,
+    Job::GNB,
+    Job::DNC,
+];
+
+// Jobs parse from their abbreviation, ignoring case, eg. "drg".
+impl std::str::FromStr for Job {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        JOBS.iter()
+            .find(|job| format!("{:?}", job).eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| format!("Unknown job: {}", s))
+    }
+}
+
 impl Job {
     pub fn primary_stat(&self) -> Stat {
         match self {
@@ -81,12 +124,23 @@ impl Job {
         }
     }
 
-    pub fn is_tank(self) -> bool {
+    // auto_attack_potency is the potency of the job's auto-attacks, or 0 for jobs whose
+    // auto-attacks aren't simulated.
+    pub fn auto_attack_potency(&self) -> i64 {
         match self {
-            Job::GLA | Job::MRD | Job::PLD | Job::WAR | Job::DRK | Job::GNB => true,
-            _ => false,
+            Job::ARC | Job::BRD | Job::MCH | Job::DNC => 100,
+            Job::GLA | Job::MRD | Job::PLD | Job::WAR | Job::DRK | Job::GNB => 110,
+            Job::PGL | Job::LNC | Job::ROG | Job::MNK | Job::DRG | Job::NIN | Job::SAM => 110,
+            _ => 0,
         }
     }
+
+    pub fn is_tank(self) -> bool {
+        matches!(
+            self,
+            Job::GLA | Job::MRD | Job::PLD | Job::WAR | Job::DRK | Job::GNB
+        )
+    }
 }
 
 macro_rules! job_stat_match {
@@ -0,0 +1,65 @@
+use super::action::Action;
+use crate::sim::SimTime;
+use bevy_ecs::prelude::Entity;
+
+/// Cast is an action being cast, whose results apply once it completes.
+#[derive(Clone)]
+pub struct Cast {
+    pub action: Action,
+    pub target: Entity,
+    pub completes_at: SimTime,
+}
+
+/// Casting is the cast an actor is busy with, if any. The actor can't act until it completes.
+#[derive(Default)]
+pub struct Casting(Option<Cast>);
+
+impl Casting {
+    pub fn start(&mut self, cast: Cast) {
+        self.0 = Some(cast);
+    }
+
+    pub fn is_casting(&self) -> bool {
+        self.0.is_some()
+    }
+
+    // is_casting_at is whether a cast is still in progress at sim_time, even if it hasn't been
+    // completed yet.
+    pub fn is_casting_at(&self, sim_time: SimTime) -> bool {
+        matches!(&self.0, Some(cast) if cast.completes_at > sim_time)
+    }
+
+    // complete ends the cast and returns it once it has completed by sim_time.
+    pub fn complete(&mut self, sim_time: SimTime) -> Option<Cast> {
+        match &self.0 {
+            Some(cast) if cast.completes_at <= sim_time => self.0.take(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complete() {
+        let mut casting = Casting::default();
+        assert!(!casting.is_casting());
+        assert!(casting.complete(0).is_none());
+        casting.start(Cast {
+            action: Action {
+                id: 1,
+                ..Default::default()
+            },
+            target: Entity::new(2),
+            completes_at: 1500,
+        });
+        assert!(casting.is_casting());
+        assert!(casting.is_casting_at(1499));
+        assert!(!casting.is_casting_at(1500));
+        assert!(casting.complete(1499).is_none());
+        assert_eq!(1, casting.complete(1500).unwrap().action.id);
+        assert!(!casting.is_casting());
+    }
+}
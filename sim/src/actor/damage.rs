@@ -1,28 +1,206 @@
-#[derive(Default)]
-pub struct Damage(i64);
+use bevy_ecs::prelude::Entity;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// HitType is how a single hit of damage landed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+pub enum HitType {
+    Normal,
+    CriticalHit,
+    DirectHit,
+    CriticalDirectHit,
+}
+
+impl HitType {
+    pub fn new(critical_hit: bool, direct_hit: bool) -> Self {
+        match (critical_hit, direct_hit) {
+            (false, false) => HitType::Normal,
+            (true, false) => HitType::CriticalHit,
+            (false, true) => HitType::DirectHit,
+            (true, true) => HitType::CriticalDirectHit,
+        }
+    }
+
+    pub fn is_critical_hit(&self) -> bool {
+        matches!(self, HitType::CriticalHit | HitType::CriticalDirectHit)
+    }
+
+    pub fn is_direct_hit(&self) -> bool {
+        matches!(self, HitType::DirectHit | HitType::CriticalDirectHit)
+    }
+}
+
+/// DamageTally sums the damage and hits of one slice of a Damage breakdown.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DamageTally {
+    pub total: i64,
+    pub hits: u64,
+    // by_hit_type holds the hits and damage of each HitType.
+    pub by_hit_type: HashMap<HitType, (u64, i64)>,
+}
+
+impl DamageTally {
+    fn add(&mut self, amount: i64, hit_type: HitType) {
+        self.total += amount;
+        self.hits += 1;
+        let (hits, damage) = self.by_hit_type.entry(hit_type).or_default();
+        *hits += 1;
+        *damage += amount;
+    }
+
+    pub fn hits_of(&self, hit_type: HitType) -> u64 {
+        self.by_hit_type.get(&hit_type).map_or(0, |(hits, _)| *hits)
+    }
+
+    pub fn damage_of(&self, hit_type: HitType) -> i64 {
+        self.by_hit_type
+            .get(&hit_type)
+            .map_or(0, |(_, damage)| *damage)
+    }
+
+    pub fn average_hit(&self) -> f64 {
+        if self.hits == 0 {
+            return 0.0;
+        }
+        self.total as f64 / self.hits as f64
+    }
+
+    // critical_hit_rate is the fraction of hits that crit, including crit direct hits.
+    pub fn critical_hit_rate(&self) -> f64 {
+        self.rate(HitType::is_critical_hit)
+    }
+
+    // direct_hit_rate is the fraction of hits that direct hit, including crit direct hits.
+    pub fn direct_hit_rate(&self) -> f64 {
+        self.rate(HitType::is_direct_hit)
+    }
+
+    fn rate(&self, filter: fn(&HitType) -> bool) -> f64 {
+        if self.hits == 0 {
+            return 0.0;
+        }
+        let hits: u64 = self
+            .by_hit_type
+            .iter()
+            .filter(|(hit_type, _)| filter(hit_type))
+            .map(|(_, (hits, _))| hits)
+            .sum();
+        hits as f64 / self.hits as f64
+    }
+
+    // share_of is this tally's percentage of `total` damage.
+    pub fn share_of(&self, total: i64) -> f64 {
+        if total == 0 {
+            return 0.0;
+        }
+        self.total as f64 / total as f64 * 100.0
+    }
+}
+
+/// Damage is the damage an actor has taken, broken down by the action and source that dealt it.
+#[derive(Debug, Default, Clone)]
+pub struct Damage {
+    tally: DamageTally,
+    by_action: HashMap<u32, DamageTally>,
+    by_source: HashMap<Entity, DamageTally>,
+}
 
 impl Damage {
-    pub fn add(&mut self, amount: i64) {
-        println!("Damage dealt: {}", amount);
-        self.0 += amount;
+    pub fn add(&mut self, source: Entity, action_id: u32, amount: i64, hit_type: HitType) {
+        self.tally.add(amount, hit_type);
+        self.by_action
+            .entry(action_id)
+            .or_default()
+            .add(amount, hit_type);
+        self.by_source
+            .entry(source)
+            .or_default()
+            .add(amount, hit_type);
+    }
+
+    pub fn total(&self) -> i64 {
+        self.tally.total
+    }
+
+    pub fn tally(&self) -> &DamageTally {
+        &self.tally
+    }
+
+    pub fn by_action(&self, action_id: u32) -> Option<&DamageTally> {
+        self.by_action.get(&action_id)
+    }
+
+    pub fn by_source(&self, source: Entity) -> Option<&DamageTally> {
+        self.by_source.get(&source)
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = (&u32, &DamageTally)> {
+        self.by_action.iter()
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = (&Entity, &DamageTally)> {
+        self.by_source.iter()
     }
 }
 
-#[cfg(tests)]
+#[cfg(test)]
 mod tests {
+    use super::*;
+
     macro_rules! test_add {
-        ($test_name:ident, $sim_time:expr, $expiration:expr, $expected:expr) => {
+        ($test_name:ident, $hits:expr, $expected:expr) => {
             #[test]
-            fn $test_name() -> std::result::Result<(), String> {
-                let mut damage = Actor::default();
-                assert_eq!($starting, damage.0);
-                damage.add($damage);
-                assert_eq!($expected, damage.0);
-                Ok(())
+            fn $test_name() {
+                let mut damage = Damage::default();
+                assert_eq!(0, damage.total());
+                for (amount, hit_type) in $hits {
+                    damage.add(Entity::new(1), 1, amount, hit_type);
+                }
+                assert_eq!($expected, damage.total());
             }
         };
     }
 
-    test_add!(add, 0, 10, 10);
-    test_add!(add, 5, 5, 10);
+    test_add!(add, [(10, HitType::Normal)], 10);
+    test_add!(
+        add_to_existing,
+        [(5, HitType::Normal), (5, HitType::CriticalHit)],
+        10
+    );
+
+    #[test]
+    fn hit_type() {
+        assert_eq!(HitType::Normal, HitType::new(false, false));
+        assert_eq!(HitType::CriticalHit, HitType::new(true, false));
+        assert_eq!(HitType::DirectHit, HitType::new(false, true));
+        assert_eq!(HitType::CriticalDirectHit, HitType::new(true, true));
+    }
+
+    #[test]
+    fn breakdown() {
+        let mut damage = Damage::default();
+        damage.add(Entity::new(1), 1, 100, HitType::Normal);
+        damage.add(Entity::new(1), 1, 150, HitType::CriticalHit);
+        damage.add(Entity::new(1), 2, 125, HitType::DirectHit);
+        damage.add(Entity::new(3), 1, 200, HitType::CriticalDirectHit);
+        assert_eq!(575, damage.total());
+        assert_eq!(4, damage.tally().hits);
+
+        let true_thrust = damage.by_action(1).unwrap();
+        assert_eq!(450, true_thrust.total);
+        assert_eq!(3, true_thrust.hits);
+        assert_eq!(150.0, true_thrust.average_hit());
+        assert_eq!(2.0 / 3.0, true_thrust.critical_hit_rate());
+        assert_eq!(1.0 / 3.0, true_thrust.direct_hit_rate());
+        assert_eq!(1, true_thrust.hits_of(HitType::CriticalHit));
+        assert_eq!(200, true_thrust.damage_of(HitType::CriticalDirectHit));
+        assert_eq!(0, true_thrust.hits_of(HitType::DirectHit));
+        assert_eq!(450.0 / 575.0 * 100.0, true_thrust.share_of(damage.total()));
+
+        assert_eq!(375, damage.by_source(Entity::new(1)).unwrap().total);
+        assert_eq!(1, damage.by_source(Entity::new(3)).unwrap().hits);
+        assert!(damage.by_action(3).is_none());
+        assert_eq!(2, damage.actions().count());
+        assert_eq!(2, damage.sources().count());
+    }
 }
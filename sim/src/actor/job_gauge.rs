@@ -0,0 +1,37 @@
+use std::collections::HashMap;
+
+/// JobGauge holds the resources on a job's gauge by name, eg. "Cartridges" or "Firstminds' Focus".
+/// Resources that were never set are empty.
+#[derive(Debug, Default, Clone)]
+pub struct JobGauge(HashMap<String, i64>);
+
+impl JobGauge {
+    pub fn get(&self, name: &str) -> i64 {
+        self.0.get(name).copied().unwrap_or(0)
+    }
+
+    pub fn set(&mut self, name: &str, amount: i64) {
+        self.0.insert(name.into(), amount);
+    }
+
+    pub fn add(&mut self, name: &str, amount: i64) {
+        *self.0.entry(name.into()).or_default() += amount;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get() {
+        let mut gauge = JobGauge::default();
+        assert_eq!(0, gauge.get("Cartridges"));
+        gauge.set("Cartridges", 2);
+        assert_eq!(2, gauge.get("Cartridges"));
+        gauge.add("Cartridges", -1);
+        gauge.add("Firstminds' Focus", 1);
+        assert_eq!(1, gauge.get("Cartridges"));
+        assert_eq!(1, gauge.get("Firstminds' Focus"));
+    }
+}
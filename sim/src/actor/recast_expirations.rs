@@ -28,6 +28,37 @@ impl RecastExpirations {
     pub fn set_gcd(&mut self, expiration: SimTime) {
         self.gcd_expiration = expiration;
     }
+
+    pub fn gcd_remaining(&self, sim_time: SimTime) -> SimTime {
+        self.gcd_expiration.saturating_sub(sim_time)
+    }
+
+    // remaining is how long until the action's own recast, ignoring the GCD, expires.
+    pub fn remaining(&self, action_id: u32, sim_time: SimTime) -> SimTime {
+        self.actions
+            .get(&action_id)
+            .map_or(0, |expiration| expiration.saturating_sub(sim_time))
+    }
+
+    // ready_at is when the action can next be used, counting the GCD unless it's an oGCD.
+    pub fn ready_at(&self, action_id: u32, ogcd: bool, sim_time: SimTime) -> SimTime {
+        let gcd_remaining = if ogcd {
+            0
+        } else {
+            self.gcd_remaining(sim_time)
+        };
+        sim_time + gcd_remaining.max(self.remaining(action_id, sim_time))
+    }
+
+    // next_expiration returns the earliest recast, including the GCD, that expires after sim_time.
+    pub fn next_expiration(&self, sim_time: SimTime) -> Option<SimTime> {
+        self.actions
+            .values()
+            .chain(std::iter::once(&self.gcd_expiration))
+            .filter(|expiration| **expiration > sim_time)
+            .min()
+            .copied()
+    }
 }
 
 #[cfg(test)]
@@ -126,4 +157,29 @@ mod tests {
         };
         assert_eq!(false, recast_expirations.check_ready(0, false, 9));
     }
+
+    #[test]
+    fn next_expiration() {
+        let mut recast_expirations = RecastExpirations::default();
+        assert_eq!(None, recast_expirations.next_expiration(0));
+        recast_expirations.set(0, 45000);
+        recast_expirations.set(1, 500);
+        recast_expirations.set_gcd(2500);
+        assert_eq!(Some(500), recast_expirations.next_expiration(0));
+        assert_eq!(Some(2500), recast_expirations.next_expiration(500));
+        assert_eq!(Some(45000), recast_expirations.next_expiration(2500));
+        assert_eq!(None, recast_expirations.next_expiration(45000));
+    }
+
+    #[test]
+    fn ready_at() {
+        let mut recast_expirations = RecastExpirations::default();
+        assert_eq!(100, recast_expirations.ready_at(0, false, 100));
+        recast_expirations.set(0, 1000);
+        recast_expirations.set_gcd(2500);
+        assert_eq!(2500, recast_expirations.ready_at(0, false, 100));
+        assert_eq!(1000, recast_expirations.ready_at(0, true, 100));
+        assert_eq!(2500, recast_expirations.ready_at(1, false, 100));
+        assert_eq!(100, recast_expirations.ready_at(1, true, 100));
+    }
 }
@@ -1,18 +1,197 @@
 use super::recast_expirations::RecastExpirations;
 use super::Action;
+use crate::actor::job_gauge::JobGauge;
+use crate::actor::stat::Stats;
+use crate::actor::status_effect::StatusEffects;
 use crate::actor::ActiveCombos;
 use crate::sim::SimTime;
+use bevy_ecs::prelude::Entity;
+use delegate::delegate;
+use script::Script;
 use std::sync::Arc;
 
+pub mod dsl;
+pub mod script;
+
+/// ActorView is a read-only view of an actor's state at `sim_time`, for rotation checks.
+pub struct ActorView<'a> {
+    pub sim_time: SimTime,
+    pub entity: Entity,
+    pub recast_expirations: &'a RecastExpirations,
+    pub active_combos: &'a ActiveCombos,
+    pub status_effects: &'a StatusEffects,
+    pub stats: &'a Stats,
+    pub job_gauge: &'a JobGauge,
+    // target_status_effects are the effects on the actor's target, eg. its DoTs and debuffs.
+    pub target_status_effects: &'a StatusEffects,
+}
+
 pub trait Check {
-    fn check(&self, active_combos: &ActiveCombos) -> bool;
+    fn check(&self, actor: &ActorView) -> bool;
 }
 
 pub struct CheckCombo(pub u32);
 
 impl Check for CheckCombo {
-    fn check(&self, active_combos: &ActiveCombos) -> bool {
-        active_combos.has_action(&self.0)
+    fn check(&self, actor: &ActorView) -> bool {
+        actor.active_combos.has_action(&self.0)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    pub fn compare<T: PartialOrd>(&self, left: T, right: T) -> bool {
+        match self {
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+// CheckGcdRemaining compares the time left on the GCD against `duration`, eg. to only weave an
+// oGCD while there is room for it.
+pub struct CheckGcdRemaining {
+    pub comparison: Comparison,
+    pub duration: SimTime,
+}
+
+impl Check for CheckGcdRemaining {
+    fn check(&self, actor: &ActorView) -> bool {
+        self.comparison.compare(
+            actor.recast_expirations.gcd_remaining(actor.sim_time),
+            self.duration,
+        )
+    }
+}
+
+pub struct CheckCooldownReady(pub u32);
+
+impl Check for CheckCooldownReady {
+    fn check(&self, actor: &ActorView) -> bool {
+        actor.recast_expirations.remaining(self.0, actor.sim_time) == 0
+    }
+}
+
+// CheckCooldownReadyWithin passes once the action's recast has at most `duration` left, eg. to
+// hold a buff for a cooldown that is about to come up.
+pub struct CheckCooldownReadyWithin {
+    pub action_id: u32,
+    pub duration: SimTime,
+}
+
+impl Check for CheckCooldownReadyWithin {
+    fn check(&self, actor: &ActorView) -> bool {
+        actor
+            .recast_expirations
+            .remaining(self.action_id, actor.sim_time)
+            <= self.duration
+    }
+}
+
+// CheckHasStatus passes while the actor has the named status, from any source.
+pub struct CheckHasStatus(pub String);
+
+impl Check for CheckHasStatus {
+    fn check(&self, actor: &ActorView) -> bool {
+        actor
+            .status_effects
+            .active(&self.0, actor.sim_time)
+            .next()
+            .is_some()
+    }
+}
+
+// CheckStatusRemaining compares the time left on the actor's named status against `duration`. A
+// missing status has no time left.
+pub struct CheckStatusRemaining {
+    pub name: String,
+    pub comparison: Comparison,
+    pub duration: SimTime,
+}
+
+impl Check for CheckStatusRemaining {
+    fn check(&self, actor: &ActorView) -> bool {
+        let remaining = actor
+            .status_effects
+            .active(&self.name, actor.sim_time)
+            .map(|effect| effect.expiration - actor.sim_time)
+            .max()
+            .unwrap_or(0);
+        self.comparison.compare(remaining, self.duration)
+    }
+}
+
+// CheckCombatTime compares the time since the encounter started against `duration`.
+pub struct CheckCombatTime {
+    pub comparison: Comparison,
+    pub duration: SimTime,
+}
+
+impl Check for CheckCombatTime {
+    fn check(&self, actor: &ActorView) -> bool {
+        self.comparison.compare(actor.sim_time, self.duration)
+    }
+}
+
+// CheckTargetHasDebuff passes while the target has the named status from this actor, eg. its own
+// DoT rather than another player's.
+pub struct CheckTargetHasDebuff(pub String);
+
+impl Check for CheckTargetHasDebuff {
+    fn check(&self, actor: &ActorView) -> bool {
+        actor
+            .target_status_effects
+            .active(&self.0, actor.sim_time)
+            .any(|effect| effect.source == actor.entity)
+    }
+}
+
+// CheckGauge compares a resource on the actor's job gauge against `amount`.
+pub struct CheckGauge {
+    pub name: String,
+    pub comparison: Comparison,
+    pub amount: i64,
+}
+
+impl Check for CheckGauge {
+    fn check(&self, actor: &ActorView) -> bool {
+        self.comparison
+            .compare(actor.job_gauge.get(&self.name), self.amount)
+    }
+}
+
+// CheckAnd passes when every one of its checks passes.
+pub struct CheckAnd(pub Vec<Arc<dyn Check + Send + Sync>>);
+
+impl Check for CheckAnd {
+    fn check(&self, actor: &ActorView) -> bool {
+        self.0.iter().all(|check| check.check(actor))
+    }
+}
+
+// CheckOr passes when any one of its checks passes.
+pub struct CheckOr(pub Vec<Arc<dyn Check + Send + Sync>>);
+
+impl Check for CheckOr {
+    fn check(&self, actor: &ActorView) -> bool {
+        self.0.iter().any(|check| check.check(actor))
+    }
+}
+
+pub struct CheckNot(pub Arc<dyn Check + Send + Sync>);
+
+impl Check for CheckNot {
+    fn check(&self, actor: &ActorView) -> bool {
+        !self.0.check(actor)
     }
 }
 
@@ -38,24 +217,41 @@ impl RotationEntry {
     }
 }
 
-#[derive(Default)]
-pub struct Rotation(Vec<RotationEntry>);
+#[derive(Default, Clone)]
+pub struct Rotation {
+    // script runs before the priority list, eg. an opener.
+    script: Script,
+    entries: Vec<RotationEntry>,
+}
 
 impl Rotation {
     pub fn add(&mut self, entry: RotationEntry) {
-        self.0.push(entry);
-    }
-
-    pub fn get_next_action_id(
-        &self,
-        sim_time: SimTime,
-        recast_expirations: &RecastExpirations,
-        active_combos: &ActiveCombos,
-    ) -> Option<u32> {
-        for entry in &self.0 {
-            // TODO: Check conditions, eg. buffs/combos/etc. recast_timers probably becomes cast_validator or some such
-            if recast_expirations.check_ready(entry.action_id, entry.ogcd, sim_time)
-                && entry.conditions.iter().all(|e| e.check(active_combos))
+        self.entries.push(entry);
+    }
+
+    pub fn script(&self) -> &Script {
+        &self.script
+    }
+
+    pub fn set_script(&mut self, script: Script) {
+        self.script = script;
+    }
+
+    // len and iter cover the priority list, not the script.
+    delegate! {
+        to self.entries {
+            pub fn len(&self) -> usize;
+            pub fn is_empty(&self) -> bool;
+            pub fn iter(&self) -> std::slice::Iter<'_, RotationEntry>;
+        }
+    }
+
+    pub fn get_next_action_id(&self, actor: &ActorView) -> Option<u32> {
+        for entry in &self.entries {
+            if actor
+                .recast_expirations
+                .check_ready(entry.action_id, entry.ogcd, actor.sim_time)
+                && entry.conditions.iter().all(|e| e.check(actor))
             {
                 return Some(entry.action_id);
             }
@@ -64,10 +260,64 @@ impl Rotation {
     }
 }
 
+/// ActorState owns everything an ActorView borrows, to build views outside of the ECS in tests.
+#[cfg(test)]
+pub(crate) struct ActorState {
+    pub entity: Entity,
+    pub recast_expirations: RecastExpirations,
+    pub active_combos: ActiveCombos,
+    pub status_effects: StatusEffects,
+    pub stats: Stats,
+    pub job_gauge: JobGauge,
+    pub target_status_effects: StatusEffects,
+}
+
+#[cfg(test)]
+impl Default for ActorState {
+    fn default() -> Self {
+        ActorState {
+            entity: Entity::new(1),
+            recast_expirations: Default::default(),
+            active_combos: Default::default(),
+            status_effects: Default::default(),
+            stats: Default::default(),
+            job_gauge: Default::default(),
+            target_status_effects: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl ActorState {
+    pub fn view(&self, sim_time: SimTime) -> ActorView<'_> {
+        ActorView {
+            sim_time,
+            entity: self.entity,
+            recast_expirations: &self.recast_expirations,
+            active_combos: &self.active_combos,
+            status_effects: &self.status_effects,
+            stats: &self.stats,
+            job_gauge: &self.job_gauge,
+            target_status_effects: &self.target_status_effects,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::actor::apply::ApplyCombo;
+    use crate::actor::status_effect::status::Status;
+    use crate::actor::status_effect::StatusEffect;
+
+    fn status(name: &str, duration: SimTime) -> Status {
+        Status {
+            name: name.into(),
+            duration,
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn get_next_action() -> std::result::Result<(), String> {
         let mut rotation = Rotation::default();
@@ -80,9 +330,8 @@ mod tests {
             ..Default::default()
         });
 
-        let recast_expirations = RecastExpirations::default();
-        let active_combos = ActiveCombos::default();
-        let id = rotation.get_next_action_id(10, &recast_expirations, &active_combos);
+        let actor = ActorState::default();
+        let id = rotation.get_next_action_id(&actor.view(10));
         assert_eq!(0, id.unwrap());
         Ok(())
     }
@@ -100,25 +349,176 @@ mod tests {
         });
 
         let sim_time = 10;
-        let mut recast_expirations = RecastExpirations::default();
-        recast_expirations.set(0, sim_time + 1);
+        let mut actor = ActorState::default();
+        actor.recast_expirations.set(0, sim_time + 1);
 
-        let active_combos = ActiveCombos::default();
-        let id = rotation.get_next_action_id(sim_time, &recast_expirations, &active_combos);
+        let id = rotation.get_next_action_id(&actor.view(sim_time));
         assert_eq!(1, id.unwrap());
         Ok(())
     }
 
+    #[test]
+    fn comparison() {
+        assert!(Comparison::Less.compare(1, 2));
+        assert!(!Comparison::Less.compare(2, 2));
+        assert!(Comparison::LessOrEqual.compare(2, 2));
+        assert!(Comparison::Greater.compare(3, 2));
+        assert!(!Comparison::Greater.compare(2, 2));
+        assert!(Comparison::GreaterOrEqual.compare(2, 2));
+    }
+
+    #[test]
+    fn check_gcd_remaining() {
+        let check = CheckGcdRemaining {
+            comparison: Comparison::Greater,
+            duration: 1000,
+        };
+        let mut actor = ActorState::default();
+        assert!(!check.check(&actor.view(0)));
+        actor.recast_expirations.set_gcd(2500);
+        assert!(check.check(&actor.view(0)));
+        assert!(check.check(&actor.view(1499)));
+        assert!(!check.check(&actor.view(1500)));
+    }
+
+    #[test]
+    fn check_cooldown_ready() {
+        let check = CheckCooldownReady(1);
+        let mut actor = ActorState::default();
+        assert!(check.check(&actor.view(0)));
+        actor.recast_expirations.set(1, 100);
+        actor.recast_expirations.set_gcd(200);
+        assert!(!check.check(&actor.view(0)));
+        assert!(check.check(&actor.view(100)));
+    }
+
+    #[test]
+    fn check_cooldown_ready_within() {
+        let check = CheckCooldownReadyWithin {
+            action_id: 1,
+            duration: 1000,
+        };
+        let mut actor = ActorState::default();
+        assert!(check.check(&actor.view(0)));
+        actor.recast_expirations.set(1, 5000);
+        assert!(!check.check(&actor.view(3999)));
+        assert!(check.check(&actor.view(4000)));
+    }
+
+    #[test]
+    fn check_has_status() {
+        let check = CheckHasStatus("Power Surge".into());
+        let mut actor = ActorState::default();
+        assert!(!check.check(&actor.view(0)));
+        actor.status_effects.add(StatusEffect::new(
+            status("Power Surge", 1000),
+            Entity::new(2),
+            0,
+        ));
+        assert!(check.check(&actor.view(999)));
+        assert!(!check.check(&actor.view(1000)));
+    }
+
+    #[test]
+    fn check_status_remaining() {
+        let check = CheckStatusRemaining {
+            name: "Power Surge".into(),
+            comparison: Comparison::Less,
+            duration: 500,
+        };
+        let mut actor = ActorState::default();
+        assert!(check.check(&actor.view(0)));
+        actor.status_effects.add(StatusEffect::new(
+            status("Power Surge", 1000),
+            actor.entity,
+            0,
+        ));
+        assert!(!check.check(&actor.view(500)));
+        assert!(check.check(&actor.view(501)));
+    }
+
+    #[test]
+    fn check_combat_time() {
+        let check = CheckCombatTime {
+            comparison: Comparison::GreaterOrEqual,
+            duration: 10000,
+        };
+        let actor = ActorState::default();
+        assert!(!check.check(&actor.view(9999)));
+        assert!(check.check(&actor.view(10000)));
+    }
+
+    #[test]
+    fn check_target_has_debuff() {
+        let check = CheckTargetHasDebuff("Chaos Thrust".into());
+        let mut actor = ActorState::default();
+        actor.target_status_effects.add(StatusEffect::new(
+            status("Chaos Thrust", 24000),
+            Entity::new(2),
+            0,
+        ));
+        assert!(!check.check(&actor.view(0)));
+        actor.target_status_effects.add(StatusEffect::new(
+            status("Chaos Thrust", 24000),
+            actor.entity,
+            0,
+        ));
+        assert!(check.check(&actor.view(0)));
+        assert!(!check.check(&actor.view(24000)));
+    }
+
+    #[test]
+    fn check_gauge() {
+        let check = CheckGauge {
+            name: "Cartridges".into(),
+            comparison: Comparison::GreaterOrEqual,
+            amount: 1,
+        };
+        let mut actor = ActorState::default();
+        assert!(!check.check(&actor.view(0)));
+        actor.job_gauge.set("Cartridges", 1);
+        assert!(check.check(&actor.view(0)));
+    }
+
+    #[test]
+    fn check_combinators() {
+        let combo: Arc<dyn Check + Send + Sync> = Arc::new(CheckCombo(1));
+        let power_surge: Arc<dyn Check + Send + Sync> =
+            Arc::new(CheckHasStatus("Power Surge".into()));
+        // combo(1) || (has_status(Power Surge) && !combo(1))
+        let check = CheckOr(vec![
+            combo.clone(),
+            Arc::new(CheckAnd(vec![
+                power_surge.clone(),
+                Arc::new(CheckNot(combo.clone())),
+            ])),
+        ]);
+        let mut actor = ActorState::default();
+        assert!(!check.check(&actor.view(0)));
+        actor.active_combos.add_action(1);
+        assert!(check.check(&actor.view(0)));
+        assert!(!CheckAnd(vec![combo.clone(), power_surge.clone()]).check(&actor.view(0)));
+        actor.active_combos.reset();
+        actor.status_effects.add(StatusEffect::new(
+            status("Power Surge", 1000),
+            actor.entity,
+            0,
+        ));
+        assert!(check.check(&actor.view(0)));
+        assert!(CheckAnd(vec![]).check(&actor.view(0)));
+        assert!(!CheckOr(vec![]).check(&actor.view(0)));
+    }
+
     #[test]
     fn get_next_action_first_failed_condition() {
         let vorpal_thrust = Action {
             id: 2,
-            name: "Vorpal Thrust",
+            name: "Vorpal Thrust".into(),
             ..Default::default()
         };
         let true_thrust = Action {
             id: 1,
-            name: "True Thrust",
+            name: "True Thrust".into(),
             results: vec![Arc::new(ApplyCombo(1))],
             ..Default::default()
         };
@@ -126,12 +526,11 @@ mod tests {
         rotation.add(RotationEntry::new(&vorpal_thrust).with_condition(Arc::new(CheckCombo(1))));
         rotation.add(RotationEntry::new(&true_thrust));
 
-        let recast_expirations = RecastExpirations::default();
-        let mut active_combos = ActiveCombos::default();
-        let id = rotation.get_next_action_id(0, &recast_expirations, &active_combos);
+        let mut actor = ActorState::default();
+        let id = rotation.get_next_action_id(&actor.view(0));
         assert_eq!(1, id.unwrap());
-        active_combos.add_action(1);
-        let id = rotation.get_next_action_id(0, &recast_expirations, &active_combos);
+        actor.active_combos.add_action(1);
+        let id = rotation.get_next_action_id(&actor.view(0));
         assert_eq!(2, id.unwrap());
     }
 }
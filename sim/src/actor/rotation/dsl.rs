@@ -0,0 +1,746 @@
+use super::script::{Script, ScriptStep};
+use super::{
+    Check, CheckAnd, CheckCombatTime, CheckCombo, CheckCooldownReady, CheckCooldownReadyWithin,
+    CheckGauge, CheckGcdRemaining, CheckHasStatus, CheckNot, CheckOr, CheckStatusRemaining,
+    CheckTargetHasDebuff, Comparison, Rotation, RotationEntry,
+};
+use crate::actor::action::{Action, Actions};
+use crate::sim::SimTime;
+use std::sync::Arc;
+
+type Condition = Arc<dyn Check + Send + Sync>;
+
+/// ParseError is a problem in a rotation file, at a 1-based line and column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// parse reads a priority list with one entry per line, eg.
+// `use Vorpal Thrust if combo(True Thrust)` or
+// `use Life Surge if gcd_remaining > 1.0 && cooldown_ready`.
+// Blank lines and lines starting with `#` are ignored. Conditions are:
+// - `combo(Action)`
+// - `cooldown_ready` or `cooldown_ready(Action)`
+// - `cooldown_ready_within(Action, seconds)`
+// - `gcd_remaining <op> seconds` and `combat_time <op> seconds`
+// - `has_status(Status)` and `status_remaining(Status) <op> seconds`
+// - `target_has_debuff(Status)`
+// - `gauge(Resource) <op> amount`
+// where `<op>` is one of `<`, `<=`, `>` or `>=`. Conditions combine with `&&`, `||`, `!` and
+// parentheses, and `&&` binds tighter than `||`.
+// Lines like `step True Thrust at 2.5` make up a script, eg. an opener, that runs in order before
+// the priority list. Steps without `at` follow the previous step as soon as they're ready.
+pub fn parse(source: &str, actions: &Actions) -> Result<Rotation, ParseError> {
+    let mut rotation = Rotation::default();
+    let mut script = Script::default();
+    for (index, text) in source.lines().enumerate() {
+        let mut cursor = Cursor {
+            text,
+            position: 0,
+            line: index + 1,
+            actions,
+        };
+        cursor.skip_whitespace();
+        if cursor.at_end() || cursor.rest().starts_with('#') {
+            continue;
+        }
+        if cursor.keyword("step") {
+            script.add(cursor.step()?);
+        } else {
+            rotation.add(cursor.entry()?);
+        }
+    }
+    rotation.set_script(script);
+    Ok(rotation)
+}
+
+// parse_conditions reads just the conditions of an entry for `action`, eg.
+// `has_status(Power Surge) || !combo(True Thrust)`, as they'd appear after its `if`.
+pub fn parse_conditions(
+    source: &str,
+    action: &Action,
+    actions: &Actions,
+) -> Result<Vec<Condition>, ParseError> {
+    let mut cursor = Cursor {
+        text: source,
+        position: 0,
+        line: 1,
+        actions,
+    };
+    cursor.end_of_conditions(action)
+}
+
+// Cursor walks a single line of a rotation file.
+struct Cursor<'a> {
+    text: &'a str,
+    // position is a byte offset into text.
+    position: usize,
+    line: usize,
+    actions: &'a Actions,
+}
+
+impl<'a> Cursor<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn at_end(&self) -> bool {
+        self.rest().trim().is_empty()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn error_at(&self, position: usize, message: String) -> ParseError {
+        ParseError {
+            line: self.line,
+            column: self.text[..position].chars().count() + 1,
+            message,
+        }
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        self.error_at(self.position, message)
+    }
+
+    // keyword consumes `word` if it comes next as a whole word.
+    fn keyword(&mut self, word: &str) -> bool {
+        let rest = self.rest();
+        if rest.starts_with(word) && !rest[word.len()..].starts_with(is_identifier_char) {
+            self.position += word.len();
+            return true;
+        }
+        false
+    }
+
+    fn symbol(&mut self, symbol: &str) -> bool {
+        if self.rest().starts_with(symbol) {
+            self.position += symbol.len();
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ParseError> {
+        if self.symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`", symbol)))
+        }
+    }
+
+    fn identifier(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !is_identifier_char(c))
+            .unwrap_or(rest.len());
+        self.position += len;
+        &rest[..len]
+    }
+
+    // action looks up the action named by the text up to byte offset `end`.
+    fn action(&mut self, end: usize) -> Result<&'a Action, ParseError> {
+        let start = self.position;
+        let name = self.text[start..end].trim();
+        self.position = end;
+        if name.is_empty() {
+            return Err(self.error_at(start, "expected an action name".into()));
+        }
+        self.actions
+            .find(name)
+            .ok_or_else(|| self.error_at(start, format!("unknown action `{}`", name)))
+    }
+
+    // step reads the rest of a `step` line.
+    fn step(&mut self) -> Result<ScriptStep, ParseError> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let end = self.position + find_keyword(rest, "at").unwrap_or(rest.len());
+        let action = self.action(end)?;
+        let at = if self.keyword("at") {
+            Some(self.seconds()?)
+        } else {
+            None
+        };
+        self.skip_whitespace();
+        if !self.at_end() {
+            return Err(self.error("expected the end of the line".into()));
+        }
+        Ok(ScriptStep::new(action, at))
+    }
+
+    fn entry(&mut self) -> Result<RotationEntry, ParseError> {
+        if !self.keyword("use") {
+            return Err(self.error("expected `use` or `step`".into()));
+        }
+        self.skip_whitespace();
+        let rest = self.rest();
+        let end = self.position + find_keyword(rest, "if").unwrap_or(rest.len());
+        let action = self.action(end)?;
+        let mut entry = RotationEntry::new(action);
+        if self.keyword("if") {
+            for condition in self.end_of_conditions(action)? {
+                entry = entry.with_condition(condition);
+            }
+        }
+        Ok(entry)
+    }
+
+    // end_of_conditions reads conditions up to the end of the line.
+    fn end_of_conditions(&mut self, action: &Action) -> Result<Vec<Condition>, ParseError> {
+        let conditions = self.conditions(action)?;
+        if !self.at_end() {
+            return Err(self.error("expected `&&`, `||` or the end of the line".into()));
+        }
+        Ok(conditions)
+    }
+
+    // conditions reads alternatives separated by `||`. A lone chain of `&&` comes back as one check
+    // per term, so each shows up as a condition of its entry.
+    fn conditions(&mut self, action: &Action) -> Result<Vec<Condition>, ParseError> {
+        let mut alternatives = vec![self.all(action)?];
+        while self.symbol("||") {
+            alternatives.push(self.all(action)?);
+        }
+        if alternatives.len() == 1 {
+            return Ok(alternatives.remove(0));
+        }
+        let alternatives = alternatives.into_iter().map(combine).collect();
+        Ok(vec![Arc::new(CheckOr(alternatives))])
+    }
+
+    // all reads terms separated by `&&`.
+    fn all(&mut self, action: &Action) -> Result<Vec<Condition>, ParseError> {
+        let mut terms = vec![self.term(action)?];
+        while self.symbol("&&") {
+            terms.push(self.term(action)?);
+        }
+        Ok(terms)
+    }
+
+    // term reads a negated or parenthesized group, or a single condition.
+    fn term(&mut self, action: &Action) -> Result<Condition, ParseError> {
+        self.skip_whitespace();
+        let term: Condition = if self.symbol("!") {
+            Arc::new(CheckNot(self.term(action)?))
+        } else if self.symbol("(") {
+            let conditions = self.conditions(action)?;
+            self.expect(")")?;
+            combine(conditions)
+        } else {
+            self.condition(action)?
+        };
+        self.skip_whitespace();
+        Ok(term)
+    }
+
+    // condition reads a single check. `action` is the action of the entry being parsed.
+    fn condition(&mut self, action: &Action) -> Result<Condition, ParseError> {
+        let start = self.position;
+        match self.identifier() {
+            "combo" => Ok(Arc::new(CheckCombo(self.argument()?.id))),
+            "cooldown_ready" => {
+                self.skip_whitespace();
+                let action_id = if self.rest().starts_with('(') {
+                    self.argument()?.id
+                } else {
+                    action.id
+                };
+                Ok(Arc::new(CheckCooldownReady(action_id)))
+            }
+            "cooldown_ready_within" => {
+                let end = self.open_argument()?;
+                let comma = self.text[self.position..end]
+                    .rfind(',')
+                    .map(|len| self.position + len)
+                    .ok_or_else(|| self.error_at(end, "expected `,`".into()))?;
+                let action_id = self.action(comma)?.id;
+                self.expect(",")?;
+                let duration = self.seconds()?;
+                self.skip_whitespace();
+                self.expect(")")?;
+                Ok(Arc::new(CheckCooldownReadyWithin {
+                    action_id,
+                    duration,
+                }))
+            }
+            "gcd_remaining" => Ok(Arc::new(CheckGcdRemaining {
+                comparison: self.comparison()?,
+                duration: self.seconds()?,
+            })),
+            "combat_time" => Ok(Arc::new(CheckCombatTime {
+                comparison: self.comparison()?,
+                duration: self.seconds()?,
+            })),
+            "has_status" => Ok(Arc::new(CheckHasStatus(self.name()?.into()))),
+            "status_remaining" => Ok(Arc::new(CheckStatusRemaining {
+                name: self.name()?.into(),
+                comparison: self.comparison()?,
+                duration: self.seconds()?,
+            })),
+            "target_has_debuff" => Ok(Arc::new(CheckTargetHasDebuff(self.name()?.into()))),
+            "gauge" => Ok(Arc::new(CheckGauge {
+                name: self.name()?.into(),
+                comparison: self.comparison()?,
+                amount: self.amount()?,
+            })),
+            "" => Err(self.error_at(start, "expected a condition".into())),
+            other => Err(self.error_at(start, format!("unknown condition `{}`", other))),
+        }
+    }
+
+    // argument reads an action name in parentheses, eg. `(True Thrust)`.
+    fn argument(&mut self) -> Result<&'a Action, ParseError> {
+        let end = self.open_argument()?;
+        let action = self.action(end)?;
+        self.expect(")")?;
+        Ok(action)
+    }
+
+    // name reads a status or gauge resource name in parentheses, eg. `(Power Surge)`.
+    fn name(&mut self) -> Result<&'a str, ParseError> {
+        let end = self.open_argument()?;
+        let name = self.text[self.position..end].trim();
+        if name.is_empty() {
+            return Err(self.error("expected a name".into()));
+        }
+        self.position = end;
+        self.expect(")")?;
+        Ok(name)
+    }
+
+    // open_argument consumes the `(` of an argument and returns the byte offset of its `)`.
+    fn open_argument(&mut self) -> Result<usize, ParseError> {
+        self.skip_whitespace();
+        self.expect("(")?;
+        self.skip_whitespace();
+        match self.rest().find(')') {
+            Some(len) => Ok(self.position + len),
+            None => Err(self.error_at(self.text.len(), "expected `)`".into())),
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Comparison, ParseError> {
+        self.skip_whitespace();
+        for (symbol, comparison) in [
+            (">=", Comparison::GreaterOrEqual),
+            ("<=", Comparison::LessOrEqual),
+            (">", Comparison::Greater),
+            ("<", Comparison::Less),
+        ] {
+            if self.symbol(symbol) {
+                return Ok(comparison);
+            }
+        }
+        Err(self.error("expected one of `<`, `<=`, `>` or `>=`".into()))
+    }
+
+    // seconds reads a number of seconds, eg. `1.5`.
+    fn seconds(&mut self) -> Result<SimTime, ParseError> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        match rest[..len].parse::<f64>() {
+            Ok(seconds) => {
+                self.position += len;
+                Ok((seconds * 1000.0).round() as SimTime)
+            }
+            Err(_) => Err(self.error("expected a number of seconds".into())),
+        }
+    }
+
+    // amount reads a whole number, eg. `2` or `-1`.
+    fn amount(&mut self) -> Result<i64, ParseError> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .char_indices()
+            .find(|(index, c)| !(c.is_ascii_digit() || (*index == 0 && *c == '-')))
+            .map_or(rest.len(), |(index, _)| index);
+        match rest[..len].parse::<i64>() {
+            Ok(amount) => {
+                self.position += len;
+                Ok(amount)
+            }
+            Err(_) => Err(self.error("expected a whole number".into())),
+        }
+    }
+}
+
+fn combine(mut conditions: Vec<Condition>) -> Condition {
+    if conditions.len() == 1 {
+        conditions.remove(0)
+    } else {
+        Arc::new(CheckAnd(conditions))
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// find_keyword finds the `keyword` that ends an action name, since names can contain spaces.
+fn find_keyword(text: &str, keyword: &str) -> Option<usize> {
+    text.match_indices(keyword)
+        .map(|(index, _)| index)
+        .find(|index| {
+            (*index == 0 || text[..*index].ends_with(char::is_whitespace))
+                && !text[index + keyword.len()..].starts_with(is_identifier_char)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::rotation::ActorState;
+    use crate::actor::status_effect::status::Status;
+    use crate::actor::status_effect::StatusEffect;
+
+    fn actions() -> Actions {
+        let mut actions = Actions::default();
+        for (id, name, ogcd) in [
+            (0, "Life Surge", true),
+            (1, "True Thrust", false),
+            (2, "Vorpal Thrust", false),
+        ] {
+            actions.add(Action {
+                id,
+                name: name.into(),
+                ogcd,
+                ..Default::default()
+            });
+        }
+        actions
+    }
+
+    fn parse_error(source: &str) -> ParseError {
+        match parse(source, &actions()) {
+            Ok(_) => panic!("Expected {:?} not to parse.", source),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn parse_rotation() {
+        let rotation = parse(
+            "# Dragoon\n\
+             use Life Surge if gcd_remaining > 1.0 && cooldown_ready\n\
+             \n\
+             use Vorpal Thrust if combo(True Thrust)\n  \
+             use True Thrust\n",
+            &actions(),
+        )
+        .unwrap();
+        assert_eq!(3, rotation.len());
+        let entries: Vec<(u32, bool, usize)> = rotation
+            .iter()
+            .map(|entry| (entry.action_id, entry.ogcd, entry.conditions.len()))
+            .collect();
+        assert_eq!(vec![(0, true, 2), (2, false, 1), (1, false, 0)], entries);
+
+        let mut actor = ActorState::default();
+        assert_eq!(Some(1), rotation.get_next_action_id(&actor.view(0)));
+        actor.active_combos.add_action(1);
+        assert_eq!(Some(2), rotation.get_next_action_id(&actor.view(0)));
+        actor.recast_expirations.set_gcd(2500);
+        assert_eq!(Some(0), rotation.get_next_action_id(&actor.view(0)));
+        assert_eq!(None, rotation.get_next_action_id(&actor.view(1500)));
+    }
+
+    #[test]
+    fn cooldown_ready_of_other_action() {
+        let rotation = parse("use True Thrust if cooldown_ready(Life Surge)", &actions()).unwrap();
+        let mut actor = ActorState::default();
+        actor.recast_expirations.set(0, 1000);
+        assert_eq!(None, rotation.get_next_action_id(&actor.view(0)));
+        assert_eq!(Some(1), rotation.get_next_action_id(&actor.view(1000)));
+    }
+
+    #[test]
+    fn actor_state_conditions() {
+        let rotation = parse(
+            "use Life Surge if has_status(Power Surge) && status_remaining(Power Surge) >= 5\n\
+             use Vorpal Thrust if target_has_debuff(Chaos Thrust) && gauge(Eyes) < 2\n\
+             use True Thrust if combat_time > 1.0 && cooldown_ready_within(Life Surge, 0.5)",
+            &actions(),
+        )
+        .unwrap();
+        let conditions: Vec<usize> = rotation
+            .iter()
+            .map(|entry| entry.conditions.len())
+            .collect();
+        assert_eq!(vec![2, 2, 2], conditions);
+
+        let mut actor = ActorState::default();
+        actor.recast_expirations.set(0, 2000);
+        assert_eq!(None, rotation.get_next_action_id(&actor.view(1000)));
+        assert_eq!(Some(1), rotation.get_next_action_id(&actor.view(1500)));
+        actor.target_status_effects.add(StatusEffect::new(
+            Status {
+                name: "Chaos Thrust".into(),
+                duration: 24000,
+                ..Default::default()
+            },
+            actor.entity,
+            0,
+        ));
+        assert_eq!(Some(2), rotation.get_next_action_id(&actor.view(1000)));
+        actor.job_gauge.set("Eyes", 2);
+        assert_eq!(None, rotation.get_next_action_id(&actor.view(1000)));
+    }
+
+    #[test]
+    fn combinators() {
+        let rotation = parse(
+            "use Life Surge if !(combo(True Thrust) || has_status(Power Surge)) && cooldown_ready\n\
+             use Vorpal Thrust if combo(True Thrust) && !has_status(Power Surge) || gauge(Eyes) > 1",
+            &actions(),
+        )
+        .unwrap();
+        let conditions: Vec<usize> = rotation
+            .iter()
+            .map(|entry| entry.conditions.len())
+            .collect();
+        assert_eq!(vec![2, 1], conditions);
+
+        let mut actor = ActorState::default();
+        actor.recast_expirations.set_gcd(2500);
+        assert_eq!(Some(0), rotation.get_next_action_id(&actor.view(0)));
+        actor.active_combos.add_action(1);
+        actor.recast_expirations.set_gcd(0);
+        assert_eq!(Some(2), rotation.get_next_action_id(&actor.view(0)));
+        actor.status_effects.add(StatusEffect::new(
+            Status {
+                name: "Power Surge".into(),
+                duration: 1000,
+                ..Default::default()
+            },
+            actor.entity,
+            0,
+        ));
+        assert_eq!(None, rotation.get_next_action_id(&actor.view(0)));
+        actor.job_gauge.set("Eyes", 2);
+        assert_eq!(Some(2), rotation.get_next_action_id(&actor.view(0)));
+    }
+
+    #[test]
+    fn conditions_only() {
+        let actions = actions();
+        let life_surge = actions.find("Life Surge").unwrap();
+        let conditions =
+            parse_conditions("cooldown_ready || combo(True Thrust)", life_surge, &actions).unwrap();
+        assert_eq!(1, conditions.len());
+        assert_eq!(
+            ParseError {
+                line: 1,
+                column: 15,
+                message: "expected `&&`, `||` or the end of the line".into(),
+            },
+            parse_conditions("cooldown_ready)", life_surge, &actions)
+                .err()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn script() {
+        let rotation = parse(
+            "step True Thrust at 1.5\n\
+             step Life Surge\n\
+             use True Thrust\n\
+             step Vorpal Thrust at 4",
+            &actions(),
+        )
+        .unwrap();
+        assert_eq!(1, rotation.len());
+        assert_eq!(
+            vec![
+                ScriptStep {
+                    action_id: 1,
+                    ogcd: false,
+                    at: Some(1500),
+                },
+                ScriptStep {
+                    action_id: 0,
+                    ogcd: true,
+                    at: None,
+                },
+                ScriptStep {
+                    action_id: 2,
+                    ogcd: false,
+                    at: Some(4000),
+                },
+            ],
+            rotation.script().steps
+        );
+    }
+
+    macro_rules! test_parse_error {
+        ($test_name:ident, $source:expr, $line:expr, $column:expr, $message:expr) => {
+            #[test]
+            fn $test_name() {
+                assert_eq!(
+                    ParseError {
+                        line: $line,
+                        column: $column,
+                        message: $message.into(),
+                    },
+                    parse_error($source)
+                );
+            }
+        };
+    }
+
+    test_parse_error!(
+        missing_use,
+        "use True Thrust\ncast Vorpal Thrust",
+        2,
+        1,
+        "expected `use` or `step`"
+    );
+    test_parse_error!(
+        unknown_action,
+        "use  Chaos Thrust",
+        1,
+        6,
+        "unknown action `Chaos Thrust`"
+    );
+    test_parse_error!(
+        missing_action,
+        "use if combo(True Thrust)",
+        1,
+        5,
+        "expected an action name"
+    );
+    test_parse_error!(
+        unknown_combo_action,
+        "use Vorpal Thrust if combo(Doom Spike)",
+        1,
+        28,
+        "unknown action `Doom Spike`"
+    );
+    test_parse_error!(
+        unclosed_argument,
+        "use Vorpal Thrust if combo(True Thrust",
+        1,
+        39,
+        "expected `)`"
+    );
+    test_parse_error!(
+        unknown_condition,
+        "use Life Surge if buffed",
+        1,
+        19,
+        "unknown condition `buffed`"
+    );
+    test_parse_error!(
+        missing_comparison,
+        "use Life Surge if gcd_remaining 1.0",
+        1,
+        33,
+        "expected one of `<`, `<=`, `>` or `>=`"
+    );
+    test_parse_error!(
+        missing_seconds,
+        "use Life Surge if gcd_remaining > soon",
+        1,
+        35,
+        "expected a number of seconds"
+    );
+    test_parse_error!(
+        missing_status,
+        "use Life Surge if has_status()",
+        1,
+        30,
+        "expected a name"
+    );
+    test_parse_error!(
+        missing_amount,
+        "use Life Surge if gauge(Eyes) > two",
+        1,
+        33,
+        "expected a whole number"
+    );
+    test_parse_error!(
+        unknown_cooldown_action,
+        "use Life Surge if cooldown_ready_within(Lance Charge, 1.0)",
+        1,
+        41,
+        "unknown action `Lance Charge`"
+    );
+    test_parse_error!(
+        missing_cooldown_action,
+        "use Life Surge if cooldown_ready_within(1.0)",
+        1,
+        44,
+        "expected `,`"
+    );
+    test_parse_error!(
+        unclosed_group,
+        "use Life Surge if (cooldown_ready || combo(True Thrust)",
+        1,
+        56,
+        "expected `)`"
+    );
+    test_parse_error!(
+        missing_negated_condition,
+        "use Life Surge if !",
+        1,
+        20,
+        "expected a condition"
+    );
+    test_parse_error!(
+        unknown_step_action,
+        "step Doom Spike at 1",
+        1,
+        6,
+        "unknown action `Doom Spike`"
+    );
+    test_parse_error!(
+        missing_step_time,
+        "step True Thrust at",
+        1,
+        20,
+        "expected a number of seconds"
+    );
+    test_parse_error!(
+        trailing_step_text,
+        "step True Thrust at 1.0 if combo(True Thrust)",
+        1,
+        25,
+        "expected the end of the line"
+    );
+    test_parse_error!(
+        trailing_text,
+        "use Life Surge if cooldown_ready and more",
+        1,
+        34,
+        "expected `&&`, `||` or the end of the line"
+    );
+    test_parse_error!(
+        missing_condition,
+        "use Life Surge if cooldown_ready && ",
+        1,
+        37,
+        "expected a condition"
+    );
+}
@@ -0,0 +1,245 @@
+use super::ActorView;
+use crate::actor::action::Action;
+use crate::sim::SimTime;
+
+/// ScriptStep is one action of a Script, optionally at a fixed timestamp.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptStep {
+    pub action_id: u32,
+    pub ogcd: bool,
+    // at is the earliest timestamp to use the action at. Without it the action follows the
+    // previous step as soon as it is ready.
+    pub at: Option<SimTime>,
+}
+
+impl ScriptStep {
+    pub fn new(action: &Action, at: Option<SimTime>) -> Self {
+        ScriptStep {
+            action_id: action.id,
+            ogcd: action.ogcd,
+            at,
+        }
+    }
+
+    fn is_ready(&self, actor: &ActorView) -> bool {
+        actor
+            .recast_expirations
+            .check_ready(self.action_id, self.ogcd, actor.sim_time)
+    }
+}
+
+/// ScriptMode is what a Script does when a step's action isn't ready when it is scheduled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ScriptMode {
+    // Wait delays the rest of the script until the action is ready.
+    #[default]
+    Wait,
+    // Validate also waits, but reports the step as late so a planned rotation can be checked.
+    Validate,
+}
+
+/// Script is an exact sequence of actions, eg. an opener or a planned rotation to replay, that
+/// runs before the priority list takes over.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Script {
+    pub steps: Vec<ScriptStep>,
+    pub mode: ScriptMode,
+}
+
+impl Script {
+    pub fn add(&mut self, step: ScriptStep) {
+        self.steps.push(step);
+    }
+
+    pub fn with_mode(mut self, mode: ScriptMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+/// ScriptAction is what an actor running a Script should do at the current timestamp.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScriptAction {
+    // Use the current step's action.
+    Use(u32),
+    // WaitUntil holds every action until the current step's timestamp.
+    WaitUntil(SimTime),
+    // WaitForReady holds every action until the current step's action comes off cooldown.
+    WaitForReady,
+    // Done hands over to the priority list.
+    Done,
+}
+
+/// ScriptProgress is how far an actor is through its rotation's Script.
+#[derive(Debug, Default)]
+pub struct ScriptProgress {
+    step: usize,
+    // reported is set once the current step has been reported as late.
+    reported: bool,
+}
+
+impl ScriptProgress {
+    // next returns what to do for the current step, advancing past it when its action is used.
+    pub fn next(&mut self, script: &Script, actor: &ActorView) -> ScriptAction {
+        let step = match script.steps.get(self.step) {
+            Some(step) => step,
+            None => return ScriptAction::Done,
+        };
+        match step.at {
+            Some(at) if actor.sim_time < at => ScriptAction::WaitUntil(at),
+            _ if !step.is_ready(actor) => ScriptAction::WaitForReady,
+            _ => {
+                self.step += 1;
+                self.reported = false;
+                ScriptAction::Use(step.action_id)
+            }
+        }
+    }
+
+    // late returns the current step the first time it is found not ready at its scheduled time,
+    // either because its action is on cooldown or because earlier steps ran past its timestamp.
+    // Steps without a timestamp are only late while their own recast runs, since waiting for the
+    // GCD is expected. Only scripts in Validate mode report late steps.
+    pub fn late<'a>(&mut self, script: &'a Script, actor: &ActorView) -> Option<&'a ScriptStep> {
+        let step = script.steps.get(self.step)?;
+        if script.mode != ScriptMode::Validate || self.reported {
+            return None;
+        }
+        let late = match step.at {
+            Some(at) if actor.sim_time < at => false,
+            Some(at) => actor.sim_time > at || !step.is_ready(actor),
+            None => {
+                actor
+                    .recast_expirations
+                    .remaining(step.action_id, actor.sim_time)
+                    > 0
+            }
+        };
+        self.reported = late;
+        late.then_some(step)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::rotation::ActorState;
+
+    fn script(mode: ScriptMode) -> Script {
+        Script {
+            steps: vec![
+                ScriptStep {
+                    action_id: 1,
+                    ogcd: false,
+                    at: Some(1000),
+                },
+                ScriptStep {
+                    action_id: 2,
+                    ogcd: true,
+                    at: None,
+                },
+            ],
+            mode,
+        }
+    }
+
+    #[test]
+    fn next() {
+        let script = script(ScriptMode::Wait);
+        let mut progress = ScriptProgress::default();
+        let mut actor = ActorState::default();
+        assert_eq!(
+            ScriptAction::WaitUntil(1000),
+            progress.next(&script, &actor.view(0))
+        );
+        assert_eq!(
+            ScriptAction::Use(1),
+            progress.next(&script, &actor.view(1000))
+        );
+        actor.recast_expirations.set(2, 2000);
+        assert_eq!(
+            ScriptAction::WaitForReady,
+            progress.next(&script, &actor.view(1000))
+        );
+        assert_eq!(
+            ScriptAction::Use(2),
+            progress.next(&script, &actor.view(2000))
+        );
+        assert_eq!(
+            ScriptAction::Done,
+            progress.next(&script, &actor.view(2000))
+        );
+    }
+
+    #[test]
+    fn late() {
+        let script = script(ScriptMode::Validate);
+        let mut progress = ScriptProgress::default();
+        let mut actor = ActorState::default();
+        assert_eq!(None, progress.late(&script, &actor.view(0)));
+        actor.recast_expirations.set_gcd(1500);
+        assert_eq!(
+            Some(&script.steps[0]),
+            progress.late(&script, &actor.view(1000))
+        );
+        // A late step is only reported once.
+        assert_eq!(None, progress.late(&script, &actor.view(1200)));
+        assert_eq!(
+            ScriptAction::Use(1),
+            progress.next(&script, &actor.view(1500))
+        );
+        assert_eq!(None, progress.late(&script, &actor.view(1500)));
+    }
+
+    #[test]
+    fn late_after_previous_step() {
+        let script = script(ScriptMode::Validate);
+        let mut progress = ScriptProgress::default();
+        let actor = ActorState::default();
+        assert_eq!(
+            Some(&script.steps[0]),
+            progress.late(&script, &actor.view(1001))
+        );
+    }
+
+    #[test]
+    fn late_without_timestamp() {
+        let script = script(ScriptMode::Validate);
+        let mut progress = ScriptProgress::default();
+        let mut actor = ActorState::default();
+        assert_eq!(
+            ScriptAction::Use(1),
+            progress.next(&script, &actor.view(1000))
+        );
+        actor.recast_expirations.set_gcd(3500);
+        actor.recast_expirations.set(2, 3000);
+        assert_eq!(
+            Some(&script.steps[1]),
+            progress.late(&script, &actor.view(1000))
+        );
+
+        let script = Script {
+            steps: vec![ScriptStep {
+                action_id: 1,
+                ogcd: false,
+                at: None,
+            }],
+            mode: ScriptMode::Validate,
+        };
+        let mut progress = ScriptProgress::default();
+        assert_eq!(None, progress.late(&script, &actor.view(1000)));
+    }
+
+    #[test]
+    fn late_wait_mode() {
+        let script = script(ScriptMode::Wait);
+        let mut progress = ScriptProgress::default();
+        let mut actor = ActorState::default();
+        actor.recast_expirations.set_gcd(1500);
+        assert_eq!(None, progress.late(&script, &actor.view(1000)));
+    }
+}
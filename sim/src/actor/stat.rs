@@ -1,6 +1,7 @@
+use serde::Deserialize;
 use std::collections::HashMap;
 #[allow(dead_code)]
-#[derive(PartialEq, Eq, Hash, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Deserialize)]
 pub enum Stat {
     // primary
     Strength,
@@ -28,14 +29,41 @@ pub enum Stat {
     // other
     PhysicalWeaponDamage,
     MagicWeaponDamage,
+    // Haste is the percent the GCD is shortened by, eg. 20 for Presence of Mind. Haste from
+    // several buffs adds up.
+    Haste,
+    // CastTimeReduction is the percent casts are shortened by, on top of speed and haste.
+    CastTimeReduction,
+    // WeaponDelay is the weapon's auto-attack delay in milliseconds, eg. 2800 for a 2.80s spear.
+    WeaponDelay,
 }
 
-#[derive(PartialEq, Eq, Hash, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Deserialize)]
 pub enum SpecialStat {
     CriticalHitPercentOverride,
+    // DirectHitPercentOverride is the chance to direct hit in percent, eg. 100 for a guaranteed
+    // direct hit.
+    DirectHitPercentOverride,
+    // CriticalHitPercentBonus and DirectHitPercentBonus are added to the chance to crit or direct
+    // hit, in percent, eg. 10 for Battle Litany. On the target they apply to hits against it, eg.
+    // Chain Stratagem.
+    CriticalHitPercentBonus,
+    DirectHitPercentBonus,
+    // InstantCast makes actions with a cast time instant, eg. Swiftcast.
+    InstantCast,
 }
 
-#[derive(Default)]
+impl SpecialStat {
+    // stacks is true for special stats that add up across statuses, rather than being set.
+    pub fn stacks(&self) -> bool {
+        matches!(
+            self,
+            SpecialStat::CriticalHitPercentBonus | SpecialStat::DirectHitPercentBonus
+        )
+    }
+}
+
+#[derive(Default, Clone)]
 pub struct Stats {
     delta: HashMap<Stat, i64>,
     base: HashMap<Stat, i64>,
@@ -69,9 +97,30 @@ impl Stats {
         self.special.insert(stat, amount);
     }
 
+    pub fn add_special(&mut self, stat: SpecialStat, amount: i64) {
+        *self.special.entry(stat).or_insert(0) += amount;
+    }
+
     pub fn get_special(&self, stat: SpecialStat) -> Option<&i64> {
         self.special.get(&stat)
     }
+
+    // against adds the crit and direct hit bonuses a target grants to hits against it to a copy
+    // of the attacker's stats.
+    pub fn against(&self, target: &Stats) -> Stats {
+        let mut stats = self.clone();
+        for stat in [
+            SpecialStat::CriticalHitPercentBonus,
+            SpecialStat::DirectHitPercentBonus,
+        ]
+        .iter()
+        {
+            if let Some(bonus) = target.get_special(*stat) {
+                stats.add_special(*stat, *bonus);
+            }
+        }
+        stats
+    }
 }
 
 #[cfg(test)]
@@ -103,6 +152,41 @@ mod tests {
         assert_eq!(10, stats.get(Stat::CriticalHitRate));
     }
 
+    #[test]
+    fn add_special() {
+        let mut stats = Stats::default();
+        stats.add_special(SpecialStat::CriticalHitPercentBonus, 10);
+        stats.add_special(SpecialStat::CriticalHitPercentBonus, 10);
+        assert_eq!(
+            Some(&20),
+            stats.get_special(SpecialStat::CriticalHitPercentBonus)
+        );
+    }
+
+    #[test]
+    fn against() {
+        let mut stats = Stats::default();
+        stats.add_special(SpecialStat::CriticalHitPercentBonus, 10);
+        stats.set_special(SpecialStat::CriticalHitPercentOverride, 100);
+        let mut target = Stats::default();
+        target.add_special(SpecialStat::CriticalHitPercentBonus, 10);
+        target.set_special(SpecialStat::DirectHitPercentOverride, 100);
+        let stats = stats.against(&target);
+        assert_eq!(
+            Some(&20),
+            stats.get_special(SpecialStat::CriticalHitPercentBonus)
+        );
+        assert_eq!(
+            Some(&100),
+            stats.get_special(SpecialStat::CriticalHitPercentOverride)
+        );
+        // Only bonuses carry over from the target.
+        assert_eq!(
+            None,
+            stats.get_special(SpecialStat::DirectHitPercentOverride)
+        );
+    }
+
     #[test]
     fn reset() {
         let mut stats = Stats::default();
@@ -1,13 +1,14 @@
 pub mod status;
 use super::Apply;
 use super::QueryActor;
+use crate::combat_log::{CombatEventKind, CombatLog};
 use crate::sim::{SimState, SimTime};
 use bevy_ecs::prelude::Entity;
 use delegate::delegate;
 use status::{Status, StatusFlag};
 
 // TODO: Maybe a time ordered heap would be faster. Benchmark when we have more functionality.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct StatusEffects(Vec<StatusEffect>);
 
 impl StatusEffects {
@@ -16,20 +17,161 @@ impl StatusEffects {
             #[call(push)]
             pub fn add(&mut self, status_effect: StatusEffect);
             pub fn len(&self) -> usize;
-            pub fn iter(&self) -> std::slice::Iter<StatusEffect>;
+            pub fn is_empty(&self) -> bool;
+            pub fn iter(&self) -> std::slice::Iter<'_, StatusEffect>;
         }
     }
-    pub fn remove_expired(&mut self, sim_time: SimTime) {
-        self.0.retain(|effect| !effect.is_expired(sim_time));
+    // add_or_refresh adds status_effect, unless the same status from the same source is already
+    // active, in which case that effect is replaced, refreshing its expiration and anything it
+    // snapshotted. Returns true on refresh.
+    pub fn add_or_refresh(&mut self, status_effect: StatusEffect) -> bool {
+        let existing = self.0.iter_mut().find(|effect| {
+            !effect.force_expired
+                && effect.source == status_effect.source
+                && effect.status.name == status_effect.status.name
+        });
+        match existing {
+            Some(effect) => {
+                *effect = status_effect;
+                true
+            }
+            None => {
+                self.0.push(status_effect);
+                false
+            }
+        }
+    }
+
+    // remove_expired removes and returns every effect that has expired by sim_time.
+    pub fn remove_expired(&mut self, sim_time: SimTime) -> Vec<StatusEffect> {
+        let (expired, active) = std::mem::take(&mut self.0)
+            .into_iter()
+            .partition(|effect| effect.is_expired(sim_time));
+        self.0 = active;
+        expired
+    }
+
+    // ticks is true while any effect acts on server ticks.
+    pub fn ticks(&self, sim_time: SimTime) -> bool {
+        self.0
+            .iter()
+            .any(|effect| !effect.is_expired(sim_time) && effect.status.ticks())
+    }
+
+    // next_expiration returns the earliest expiration after sim_time of any effect still active.
+    pub fn next_expiration(&self, sim_time: SimTime) -> Option<SimTime> {
+        self.0
+            .iter()
+            .filter(|effect| !effect.is_expired(sim_time))
+            .map(|effect| effect.expiration)
+            .min()
+    }
+
+    // active returns the effects of the named status that have not expired by sim_time.
+    pub fn active<'a>(
+        &'a self,
+        name: &'a str,
+        sim_time: SimTime,
+    ) -> impl Iterator<Item = &'a StatusEffect> {
+        self.0
+            .iter()
+            .filter(move |effect| effect.status.name == name && !effect.is_expired(sim_time))
     }
 
-    pub fn expire_with_flag(&mut self, flag: StatusFlag) {
+    // expire_with_flag expires every effect with flag, and logs it as expired at sim_time
+    // on holder, rather than when remove_expired drops it.
+    pub fn expire_with_flag(
+        &mut self,
+        flag: StatusFlag,
+        sim_time: SimTime,
+        log: &mut CombatLog,
+        holder: Entity,
+    ) {
         for effect in self.0.iter_mut() {
-            if effect.has_flag(&flag) {
+            if effect.has_flag(&flag) && !effect.force_expired {
                 effect.expire();
+                log.record(
+                    sim_time,
+                    effect.source,
+                    holder,
+                    CombatEventKind::StatusExpired {
+                        name: effect.status.name.clone(),
+                    },
+                );
             }
         }
     }
+
+    // damage_dealt lists the damage dealt multipliers of effects active at sim_time, in the order
+    // they were applied.
+    pub fn damage_dealt(&self, sim_time: SimTime) -> DamageMultipliers {
+        self.multipliers(sim_time, |effect| effect.damage_dealt())
+    }
+
+    // damage_taken lists the damage taken multipliers of effects active at sim_time, in the order
+    // they were applied.
+    pub fn damage_taken(&self, sim_time: SimTime) -> DamageMultipliers {
+        self.multipliers(sim_time, |effect| effect.damage_taken())
+    }
+
+    fn multipliers<F>(&self, sim_time: SimTime, multiplier: F) -> DamageMultipliers
+    where
+        F: Fn(&(dyn Apply + Send + Sync)) -> Option<f64>,
+    {
+        let mut multipliers = DamageMultipliers::default();
+        for effect in self.0.iter().filter(|effect| !effect.is_expired(sim_time)) {
+            for value in effect
+                .status
+                .effects
+                .iter()
+                .filter_map(|e| multiplier(e.as_ref()))
+            {
+                multipliers.add(effect.status.name.clone(), value);
+            }
+        }
+        multipliers
+    }
+}
+
+/// DamageMultipliers are the buffs and debuffs affecting a hit, in the order they are floored.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DamageMultipliers(Vec<(String, f64)>);
+
+impl DamageMultipliers {
+    pub fn add(&mut self, name: String, multiplier: f64) {
+        self.0.push((name, multiplier));
+    }
+
+    pub fn extend(&mut self, other: DamageMultipliers) {
+        self.0.extend(other.0);
+    }
+
+    pub fn values(&self) -> Vec<f64> {
+        self.0.iter().map(|(_, multiplier)| *multiplier).collect()
+    }
+
+    // names are the statuses the multipliers come from, for the combat log.
+    pub fn names(&self) -> Vec<String> {
+        self.0.iter().map(|(name, _)| name.clone()).collect()
+    }
+}
+
+// damage_multipliers collects the source's damage dealt buffs, then the target's damage taken
+// debuffs, for a hit from source to target.
+pub fn damage_multipliers(
+    sim: &SimState,
+    query: &mut QueryActor,
+    source: Entity,
+    target: Entity,
+) -> DamageMultipliers {
+    let mut multipliers = DamageMultipliers::default();
+    if let Ok((_, _, _, _, _, _, status_effects, _, _, _)) = query.get_mut(source) {
+        multipliers.extend(status_effects.damage_dealt(sim.now()));
+    }
+    if let Ok((_, _, _, _, _, _, status_effects, _, _, _)) = query.get_mut(target) {
+        multipliers.extend(status_effects.damage_taken(sim.now()));
+    }
+    multipliers
 }
 
 #[derive(Clone, Debug)]
@@ -45,12 +187,12 @@ pub struct StatusEffect {
 
 impl StatusEffect {
     pub fn new(status: Status, source: Entity, sim_time: SimTime) -> StatusEffect {
-        return StatusEffect {
+        StatusEffect {
             expiration: sim_time + status.duration,
-            status: status,
-            source: source,
+            status,
+            source,
             force_expired: false,
-        };
+        }
     }
 
     pub fn is_expired(&self, sim_time: SimTime) -> bool {
@@ -69,9 +211,16 @@ impl StatusEffect {
 }
 
 impl Apply for StatusEffect {
-    fn apply(&self, sim: &SimState, query: &mut QueryActor, source: Entity, target: Entity) {
+    fn apply(
+        &self,
+        sim: &SimState,
+        log: &mut CombatLog,
+        query: &mut QueryActor,
+        source: Entity,
+        target: Entity,
+    ) {
         for effect in &self.status.effects {
-            effect.apply(sim, query, source, target);
+            effect.apply(sim, log, query, source, target);
         }
     }
 }
@@ -81,6 +230,7 @@ mod tests {
     use super::*;
     use status::StatusFlags;
     use std::collections::HashSet;
+    use std::sync::Arc;
 
     #[test]
     fn status_effect_new() {
@@ -111,6 +261,34 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn damage_multipliers() {
+        let mut effects = StatusEffects::default();
+        let buff = |name: &str, duration, percent| Status {
+            name: name.into(),
+            duration,
+            effects: vec![Arc::new(status::ModifyDamageDealt { percent })],
+            ..Default::default()
+        };
+        effects.add(StatusEffect::new(
+            buff("Lance Charge", 20000, 15),
+            Entity::new(1),
+            0,
+        ));
+        effects.add(StatusEffect::new(Status::default(), Entity::new(1), 0));
+        effects.add(StatusEffect::new(
+            buff("Disembowel", 30000, 10),
+            Entity::new(1),
+            0,
+        ));
+        let multipliers = effects.damage_dealt(10000);
+        assert_eq!(vec![1.15, 1.1], multipliers.values());
+        assert_eq!(vec!["Lance Charge", "Disembowel"], multipliers.names());
+        assert!(effects.damage_taken(10000).values().is_empty());
+        // Expired buffs no longer count.
+        assert_eq!(vec![1.1], effects.damage_dealt(20000).values());
+    }
+
     #[test]
     fn remove_expired() {
         let mut effects = StatusEffects::default();
@@ -136,8 +314,9 @@ mod tests {
         ));
         effects.add(StatusEffect::new(should_expire.clone(), Entity::new(1), 10));
         assert_eq!(5, effects.len());
-        effects.remove_expired(11);
+        let expired = effects.remove_expired(11);
         assert_eq!(2, effects.len());
+        assert_eq!(3, expired.len());
 
         for effect in effects.iter() {
             assert_eq!(should_not_expire.name, effect.status.name);
@@ -165,7 +344,16 @@ mod tests {
 
         effects.remove_expired(11);
         assert_eq!(2, effects.len());
-        effects.expire_with_flag(StatusFlag::ExpireOnDirectDamage);
+        let mut log = CombatLog::default();
+        effects.expire_with_flag(
+            StatusFlag::ExpireOnDirectDamage,
+            11,
+            &mut log,
+            Entity::new(2),
+        );
+        // The expiration is logged when it happens, not when the effect is removed.
+        assert_eq!(1, log.len());
+        assert_eq!(11, log.iter().next().unwrap().time);
         effects.remove_expired(11);
         assert_eq!(1, effects.len());
 
@@ -173,6 +361,79 @@ mod tests {
             assert_eq!(should_not_expire.name, effect.status.name);
         }
     }
+    #[test]
+    fn add_or_refresh() {
+        let mut effects = StatusEffects::default();
+        let status = Status {
+            name: "Refreshable".into(),
+            duration: 1000,
+            ..Default::default()
+        };
+        assert!(!effects.add_or_refresh(StatusEffect::new(status.clone(), Entity::new(1), 0)));
+        assert!(effects.add_or_refresh(StatusEffect::new(status.clone(), Entity::new(1), 500)));
+        assert_eq!(1, effects.len());
+        assert_eq!(1500, effects.iter().next().unwrap().expiration);
+        assert!(!effects.add_or_refresh(StatusEffect::new(status, Entity::new(2), 500)));
+        assert_eq!(2, effects.len());
+    }
+
+    #[test]
+    fn add_or_refresh_force_expired() {
+        let mut effects = StatusEffects::default();
+        let status = Status {
+            flags: StatusFlags::new(&[StatusFlag::ExpireOnDirectDamage]),
+            ..Default::default()
+        };
+        effects.add_or_refresh(StatusEffect::new(status.clone(), Entity::new(1), 0));
+        effects.expire_with_flag(
+            StatusFlag::ExpireOnDirectDamage,
+            0,
+            &mut CombatLog::default(),
+            Entity::new(1),
+        );
+        assert!(!effects.add_or_refresh(StatusEffect::new(status, Entity::new(1), 0)));
+        assert_eq!(2, effects.len());
+    }
+
+    #[test]
+    fn next_expiration() {
+        let mut effects = StatusEffects::default();
+        assert_eq!(None, effects.next_expiration(0));
+        let status = Status {
+            duration: 1000,
+            flags: StatusFlags::new(&[StatusFlag::ExpireOnDirectDamage]),
+            ..Default::default()
+        };
+        effects.add(StatusEffect::new(status.clone(), Entity::new(1), 500));
+        effects.add(StatusEffect::new(status, Entity::new(1), 0));
+        assert_eq!(Some(1000), effects.next_expiration(0));
+        assert_eq!(Some(1500), effects.next_expiration(1000));
+        effects.expire_with_flag(
+            StatusFlag::ExpireOnDirectDamage,
+            0,
+            &mut CombatLog::default(),
+            Entity::new(1),
+        );
+        assert_eq!(None, effects.next_expiration(0));
+    }
+
+    #[test]
+    fn active() {
+        let mut effects = StatusEffects::default();
+        let status = Status {
+            name: "Power Surge".into(),
+            duration: 1000,
+            ..Default::default()
+        };
+        effects.add(StatusEffect::new(status.clone(), Entity::new(1), 0));
+        effects.add(StatusEffect::new(status, Entity::new(2), 500));
+        effects.add(StatusEffect::new(Status::default(), Entity::new(1), 0));
+        assert_eq!(2, effects.active("Power Surge", 0).count());
+        assert_eq!(1, effects.active("Power Surge", 1000).count());
+        assert_eq!(0, effects.active("Power Surge", 1500).count());
+        assert_eq!(0, effects.active("Lance Charge", 0).count());
+    }
+
     #[test]
     fn is_expired() {
         let effect = StatusEffect::new(Status::default(), Entity::new(1), 10);
@@ -1,9 +1,14 @@
-use super::super::stat::{SpecialStat, Stat};
+use super::super::calc::{self, lookup::Job, AttackType};
+use super::super::damage::HitType;
+use super::super::stat::{SpecialStat, Stat, Stats};
 use super::Apply;
+use super::DamageMultipliers;
 use super::QueryActor;
+use crate::combat_log::{CombatEventKind, CombatLog};
 use crate::sim::{SimState, SimTime};
 use bevy_ecs::prelude::Entity;
 use delegate::delegate;
+use serde::Deserialize;
 use std::collections::HashSet;
 use std::sync::Arc;
 
@@ -22,6 +27,10 @@ impl Status {
             pub fn has_flag(&self, flag: &StatusFlag) -> bool;
         }
     }
+
+    pub fn ticks(&self) -> bool {
+        self.effects.iter().any(|effect| effect.ticks())
+    }
 }
 
 impl std::fmt::Debug for Status {
@@ -33,9 +42,14 @@ impl std::fmt::Debug for Status {
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Deserialize)]
 pub enum StatusFlag {
     ExpireOnDirectDamage,
+    // ExpireOnComboBreak expires the status on direct damage from actions that don't preserve
+    // combos, eg. combo statuses.
+    ExpireOnComboBreak,
+    // ExpireOnCast expires the status once it makes a cast instant.
+    ExpireOnCast,
 }
 
 #[derive(Default, Clone)]
@@ -63,8 +77,15 @@ pub struct ModifyStat {
 }
 
 impl Apply for ModifyStat {
-    fn apply(&self, _sim: &SimState, query: &mut QueryActor, _source: Entity, target: Entity) {
-        if let Ok((_, _, _, _, _, _, _, mut stats, _)) = query.get_mut(target) {
+    fn apply(
+        &self,
+        _sim: &SimState,
+        _log: &mut CombatLog,
+        query: &mut QueryActor,
+        _source: Entity,
+        target: Entity,
+    ) {
+        if let Ok((_, _, _, _, _, _, _, mut stats, _, _)) = query.get_mut(target) {
             stats.add(self.stat, self.amount);
         }
     }
@@ -76,22 +97,148 @@ pub struct ModifySpecialStat {
 }
 
 impl Apply for ModifySpecialStat {
-    fn apply(&self, _sim: &SimState, query: &mut QueryActor, _source: Entity, target: Entity) {
-        if let Ok((_, _, _, _, _, _, _, mut stats, _)) = query.get_mut(target) {
-            stats.set_special(self.stat, self.amount);
+    fn apply(
+        &self,
+        _sim: &SimState,
+        _log: &mut CombatLog,
+        query: &mut QueryActor,
+        _source: Entity,
+        target: Entity,
+    ) {
+        if let Ok((_, _, _, _, _, _, _, mut stats, _, _)) = query.get_mut(target) {
+            if self.stat.stacks() {
+                stats.add_special(self.stat, self.amount);
+            } else {
+                stats.set_special(self.stat, self.amount);
+            }
         }
     }
 }
 
+// ModifyDamageDealt raises the damage its holder deals by a percent, eg. 15 for Lance Charge.
+pub struct ModifyDamageDealt {
+    pub percent: i64,
+}
+
+impl Apply for ModifyDamageDealt {
+    // The multiplier is read when damage is dealt, so there is nothing to apply each update.
+    fn apply(
+        &self,
+        _sim: &SimState,
+        _log: &mut CombatLog,
+        _query: &mut QueryActor,
+        _source: Entity,
+        _target: Entity,
+    ) {
+    }
+
+    fn damage_dealt(&self) -> Option<f64> {
+        Some(percent_multiplier(self.percent))
+    }
+}
+
+// ModifyDamageTaken raises the damage its holder takes by a percent, eg. for a vulnerability
+// debuff on the target.
+pub struct ModifyDamageTaken {
+    pub percent: i64,
+}
+
+impl Apply for ModifyDamageTaken {
+    fn apply(
+        &self,
+        _sim: &SimState,
+        _log: &mut CombatLog,
+        _query: &mut QueryActor,
+        _source: Entity,
+        _target: Entity,
+    ) {
+    }
+
+    fn damage_taken(&self) -> Option<f64> {
+        Some(percent_multiplier(self.percent))
+    }
+}
+
+fn percent_multiplier(percent: i64) -> f64 {
+    (100 + percent) as f64 / 100.0
+}
+
 pub struct SetCombo(pub u32);
 
 impl Apply for SetCombo {
-    fn apply(&self, _sim: &SimState, query: &mut QueryActor, source: Entity, _target: Entity) {
-        if let Ok((_, _, _, _, _, _, _, _, mut active_combos)) = query.get_mut(source) {
+    fn apply(
+        &self,
+        _sim: &SimState,
+        _log: &mut CombatLog,
+        query: &mut QueryActor,
+        source: Entity,
+        _target: Entity,
+    ) {
+        if let Ok((_, _, _, _, _, _, _, _, mut active_combos, _)) = query.get_mut(source) {
             active_combos.add_action(self.0);
         }
     }
 }
+/// DamageTick deals damage over time once every server tick, from the source's stats and the
+/// buffs and debuffs as they were when the status was applied.
+pub struct DamageTick {
+    pub action_id: u32,
+    pub potency: i64,
+    pub job: Job,
+    pub stats: Stats,
+    pub attack_type: AttackType,
+    pub multipliers: DamageMultipliers,
+}
+
+impl Apply for DamageTick {
+    fn apply(
+        &self,
+        sim: &SimState,
+        log: &mut CombatLog,
+        query: &mut QueryActor,
+        source: Entity,
+        target: Entity,
+    ) {
+        if !sim.is_server_tick() {
+            return;
+        }
+        let tick = calc::damage_over_time(
+            sim,
+            self.potency,
+            self.job,
+            &self.stats,
+            self.attack_type,
+            self.multipliers.values(),
+        );
+        if let Ok((_, _, _, _, _, mut damage, _, _, _, _)) = query.get_mut(target) {
+            damage.add(
+                source,
+                self.action_id,
+                tick.amount,
+                HitType::new(tick.critical_hit, tick.direct_hit),
+            );
+        }
+        log.record(
+            sim.now(),
+            source,
+            target,
+            CombatEventKind::DamageOverTimeTicked {
+                action_id: self.action_id,
+                potency: self.potency,
+                amount: tick.amount,
+                critical_hit: tick.critical_hit,
+                direct_hit: tick.direct_hit,
+                multipliers: self.multipliers.values(),
+                buffs: self.multipliers.names(),
+            },
+        );
+    }
+
+    fn ticks(&self) -> bool {
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -108,4 +255,10 @@ mod tests {
         };
         assert_eq!(true, status.has_flag(&StatusFlag::ExpireOnDirectDamage));
     }
+    #[test]
+    fn modify_damage() {
+        assert_eq!(Some(1.15), ModifyDamageDealt { percent: 15 }.damage_dealt());
+        assert_eq!(None, ModifyDamageDealt { percent: 15 }.damage_taken());
+        assert_eq!(Some(1.1), ModifyDamageTaken { percent: 10 }.damage_taken());
+    }
 }
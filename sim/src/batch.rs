@@ -0,0 +1,371 @@
+use crate::encounter::EndReason;
+use crate::sim::SeededRng;
+use crate::simulation::{Simulation, SimulationResult};
+use rand::random;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::thread;
+
+pub const DEFAULT_HISTOGRAM_BUCKETS: usize = 20;
+pub const DEFAULT_PERCENTILES: [f64; 7] = [1.0, 5.0, 25.0, 50.0, 75.0, 95.0, 99.0];
+
+/// Batch runs many independently seeded simulations and summarizes the spread of their DPS.
+/// `factory` builds the simulation for each run; its rng is replaced by a seed derived from the
+/// batch seed so the whole batch can be reproduced.
+pub struct Batch<F>
+where
+    F: Fn() -> Simulation + Sync,
+{
+    factory: F,
+    runs: usize,
+    seed: u64,
+    threads: usize,
+    histogram_buckets: usize,
+}
+
+impl<F> Batch<F>
+where
+    F: Fn() -> Simulation + Sync,
+{
+    pub fn new(runs: usize, factory: F) -> Self {
+        Batch {
+            factory,
+            runs,
+            seed: random::<u64>(),
+            threads: thread::available_parallelism()
+                .map(|threads| threads.get())
+                .unwrap_or(1),
+            histogram_buckets: DEFAULT_HISTOGRAM_BUCKETS,
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn with_histogram_buckets(mut self, buckets: usize) -> Self {
+        self.histogram_buckets = buckets.max(1);
+        self
+    }
+
+    // run_seed is the seed of a single run. Runs keep their seed no matter which thread runs them.
+    fn run_seed(&self, run: usize) -> u64 {
+        self.seed.wrapping_add(run as u64)
+    }
+
+    pub fn run(&self) -> BatchResult {
+        let results = Mutex::new(Vec::<(usize, RunSummary)>::with_capacity(self.runs));
+        let threads = self.threads.min(self.runs.max(1));
+        thread::scope(|scope| {
+            for worker in 0..threads {
+                let results = &results;
+                scope.spawn(move || {
+                    for run in (worker..self.runs).step_by(threads) {
+                        // Only the summary is kept, the run's event log is dropped here.
+                        let result = (self.factory)()
+                            .with_rng(SeededRng::new(self.run_seed(run)))
+                            .run();
+                        let summary = RunSummary::new(self.run_seed(run), &result);
+                        results.lock().unwrap().push((run, summary));
+                    }
+                });
+            }
+        });
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(run, _)| *run);
+        let runs: Vec<RunSummary> = results.into_iter().map(|(_, summary)| summary).collect();
+        let dps: Vec<f64> = runs.iter().map(|summary| summary.dps).collect();
+        BatchResult {
+            seed: self.seed,
+            dps: DpsStatistics::new(&dps, self.histogram_buckets),
+            runs,
+        }
+    }
+}
+
+/// RunSummary is what a batch keeps of each run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunSummary {
+    pub seed: u64,
+    pub total_damage: i64,
+    pub dps: f64,
+    pub end_reason: EndReason,
+}
+
+impl RunSummary {
+    pub fn new(seed: u64, result: &SimulationResult) -> Self {
+        RunSummary {
+            seed,
+            total_damage: result.total_damage,
+            dps: result.dps(),
+            end_reason: result.end_reason,
+        }
+    }
+}
+
+pub struct BatchResult {
+    // seed is the batch seed. Run `n` used `seed + n`.
+    pub seed: u64,
+    pub runs: Vec<RunSummary>,
+    pub dps: DpsStatistics,
+}
+
+impl std::fmt::Display for BatchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Runs: {}", self.runs.len())?;
+        writeln!(f, "Seed: {}", self.seed)?;
+        write!(f, "{}", self.dps)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DpsStatistics {
+    pub mean: f64,
+    pub median: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+    // percentiles pairs each percentile in DEFAULT_PERCENTILES with its DPS.
+    pub percentiles: Vec<(f64, f64)>,
+    pub histogram: Histogram,
+}
+
+impl DpsStatistics {
+    pub fn new(values: &[f64], histogram_buckets: usize) -> Self {
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).expect("DPS should never be NaN."));
+        DpsStatistics {
+            mean: mean(&sorted),
+            median: percentile(&sorted, 50.0),
+            std_dev: std_dev(&sorted),
+            min: sorted.first().copied().unwrap_or(0.0),
+            max: sorted.last().copied().unwrap_or(0.0),
+            percentiles: DEFAULT_PERCENTILES
+                .iter()
+                .map(|p| (*p, percentile(&sorted, *p)))
+                .collect(),
+            histogram: Histogram::new(&sorted, histogram_buckets),
+        }
+    }
+}
+
+impl std::fmt::Display for DpsStatistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Mean DPS: {:.2}", self.mean)?;
+        writeln!(f, "Median DPS: {:.2}", self.median)?;
+        writeln!(f, "Std dev: {:.2}", self.std_dev)?;
+        writeln!(f, "Min: {:.2} Max: {:.2}", self.min, self.max)?;
+        for (p, value) in &self.percentiles {
+            writeln!(f, "p{}: {:.2}", p, value)?;
+        }
+        write!(f, "{}", self.histogram)
+    }
+}
+
+/// Histogram counts values into equal width buckets between the smallest and largest value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Histogram {
+    pub min: f64,
+    pub bucket_width: f64,
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    // new uses at least one bucket.
+    pub fn new(values: &[f64], buckets: usize) -> Self {
+        let buckets = buckets.max(1);
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        if values.is_empty() {
+            return Histogram {
+                min: 0.0,
+                bucket_width: 0.0,
+                counts: vec![],
+            };
+        }
+        let bucket_width = (max - min) / buckets as f64;
+        let mut counts = vec![0; buckets];
+        for value in values {
+            let bucket = if bucket_width > 0.0 {
+                (((value - min) / bucket_width) as usize).min(buckets - 1)
+            } else {
+                0
+            };
+            counts[bucket] += 1;
+        }
+        Histogram {
+            min,
+            bucket_width,
+            counts,
+        }
+    }
+}
+
+impl std::fmt::Display for Histogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const BAR_WIDTH: usize = 40;
+        let largest = self.counts.iter().copied().max().unwrap_or(0).max(1);
+        for (bucket, count) in self.counts.iter().enumerate() {
+            let low = self.min + self.bucket_width * bucket as f64;
+            writeln!(
+                f,
+                "{:>10.2} - {:>10.2} | {:<width$} {}",
+                low,
+                low + self.bucket_width,
+                "#".repeat(count * BAR_WIDTH / largest),
+                count,
+                width = BAR_WIDTH
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+// std_dev is the sample standard deviation.
+fn std_dev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(values);
+    let variance = values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>()
+        / (values.len() - 1) as f64;
+    variance.sqrt()
+}
+
+// percentile linearly interpolates between the closest ranks of already sorted values.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = p / 100.0 * (sorted.len() - 1) as f64;
+    let low = rank.floor() as usize;
+    let high = rank.ceil() as usize;
+    sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::action::{Action, Actions};
+    use crate::actor::apply::{DoDirectDamage, StartGcd};
+    use crate::actor::calc::lookup::Job;
+    use crate::actor::rotation::{Rotation, RotationEntry};
+    use crate::actor::stat::{Stat, Stats};
+    use crate::simulation::ActorConfig;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_mean() {
+        assert_eq!(0.0, mean(&[]));
+        assert_eq!(2.5, mean(&[1.0, 2.0, 3.0, 4.0]));
+    }
+
+    #[test]
+    fn test_std_dev() {
+        assert_eq!(0.0, std_dev(&[5.0]));
+        assert_eq!(
+            2.138,
+            (std_dev(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]) * 1000.0).round() / 1000.0
+        );
+    }
+
+    #[test]
+    fn test_percentile() {
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(1.0, percentile(&values, 0.0));
+        assert_eq!(3.0, percentile(&values, 50.0));
+        assert_eq!(5.0, percentile(&values, 100.0));
+        assert_eq!(1.4, percentile(&values, 10.0));
+        assert_eq!(2.5, percentile(&[1.0, 2.0, 3.0, 4.0], 50.0));
+    }
+
+    #[test]
+    fn histogram() {
+        let histogram = Histogram::new(&[0.0, 1.0, 2.0, 3.0, 4.0, 10.0], 5);
+        assert_eq!(0.0, histogram.min);
+        assert_eq!(2.0, histogram.bucket_width);
+        assert_eq!(vec![2, 2, 1, 0, 1], histogram.counts);
+    }
+
+    #[test]
+    fn histogram_single_value() {
+        let histogram = Histogram::new(&[3.0, 3.0], 4);
+        assert_eq!(vec![2, 0, 0, 0], histogram.counts);
+    }
+
+    #[test]
+    fn histogram_no_buckets() {
+        assert_eq!(vec![2], Histogram::new(&[3.0, 3.0], 0).counts);
+        assert_eq!(vec![2], Histogram::new(&[1.0, 2.0], 0).counts);
+        assert_eq!(vec![2], DpsStatistics::new(&[1.0, 2.0], 0).histogram.counts);
+    }
+
+    fn simulation() -> Simulation {
+        let mut actions = Actions::default();
+        let mut rotation = Rotation::default();
+        let true_thrust = Action {
+            id: 1,
+            name: "True Thrust".into(),
+            results: vec![
+                Arc::new(DoDirectDamage {
+                    action_id: 1,
+                    potency: 290,
+                    ..Default::default()
+                }),
+                Arc::new(StartGcd::default()),
+            ],
+            ..Default::default()
+        };
+        rotation.add(RotationEntry::new(&true_thrust));
+        actions.add(true_thrust);
+
+        let mut stats = Stats::default();
+        stats.set_base(Stat::PhysicalWeaponDamage, 134);
+        stats.set_base(Stat::AttackPower, 5435);
+        stats.set_base(Stat::CriticalHitRate, 3543);
+        stats.set_base(Stat::DirectHitRate, 1620);
+        Simulation::new().with_actor(ActorConfig {
+            job: Job::DRG,
+            actions,
+            rotation,
+            stats,
+        })
+    }
+
+    #[test]
+    fn run() {
+        let result = Batch::new(16, simulation).with_seed(7).run();
+        assert_eq!(16, result.runs.len());
+        assert_eq!(16, result.dps.histogram.counts.iter().sum::<usize>());
+        for (run, summary) in result.runs.iter().enumerate() {
+            assert_eq!(7 + run as u64, summary.seed);
+            assert_eq!(EndReason::DurationElapsed, summary.end_reason);
+        }
+        assert!(result.dps.min <= result.dps.median);
+        assert!(result.dps.median <= result.dps.max);
+    }
+
+    #[test]
+    fn run_is_independent_of_threads() {
+        let single = Batch::new(8, simulation).with_seed(3).with_threads(1).run();
+        let multi = Batch::new(8, simulation).with_seed(3).with_threads(4).run();
+        assert_eq!(single.dps, multi.dps);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::sim::SimTime;
+use bevy_ecs::prelude::Entity;
+use serde::{Serialize, Serializer};
+
+/// CombatEventKind is what happened in a CombatEvent.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type")]
+pub enum CombatEventKind {
+    ActionUsed {
+        action_id: u32,
+        name: String,
+    },
+    DamageDealt {
+        action_id: u32,
+        potency: i64,
+        amount: i64,
+        critical_hit: bool,
+        direct_hit: bool,
+        multipliers: Vec<f64>,
+        // buffs are the statuses the multipliers came from, in the same order.
+        buffs: Vec<String>,
+    },
+    // DamageOverTimeTicked is a tick of a status applied by DoDamageOverTime.
+    DamageOverTimeTicked {
+        action_id: u32,
+        potency: i64,
+        amount: i64,
+        critical_hit: bool,
+        direct_hit: bool,
+        multipliers: Vec<f64>,
+        buffs: Vec<String>,
+    },
+    StatusApplied {
+        name: String,
+        expiration: SimTime,
+    },
+    StatusRefreshed {
+        name: String,
+        expiration: SimTime,
+    },
+    StatusExpired {
+        name: String,
+    },
+    ComboSet {
+        action_id: u32,
+    },
+    ComboConsumed {
+        action_id: u32,
+    },
+    GcdStarted {
+        duration: SimTime,
+    },
+    // ScriptStepLate is a step of a script being validated that wasn't ready when scheduled.
+    ScriptStepLate {
+        action_id: u32,
+        // scheduled is the step's timestamp, if it has one.
+        scheduled: Option<SimTime>,
+        // ready_at is when the step's action comes off cooldown.
+        ready_at: SimTime,
+    },
+    // ComboMissed is a combo action used without the combo from `action_id` active.
+    ComboMissed {
+        action_id: u32,
+    },
+    // CastStarted follows the ActionUsed of an action with a cast time. Its results apply once
+    // the cast completes.
+    CastStarted {
+        action_id: u32,
+        duration: SimTime,
+    },
+    CastCompleted {
+        action_id: u32,
+    },
+    // AnimationLockStarted stops the actor from acting again for `duration`, latency included.
+    AnimationLockStarted {
+        duration: SimTime,
+    },
+}
+
+/// CombatEvent is a single entry in the CombatLog.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CombatEvent {
+    pub time: SimTime,
+    #[serde(serialize_with = "serialize_entity")]
+    pub source: Entity,
+    #[serde(serialize_with = "serialize_entity")]
+    pub target: Entity,
+    #[serde(flatten)]
+    pub kind: CombatEventKind,
+}
+
+fn serialize_entity<S: Serializer>(entity: &Entity, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u32(entity.id())
+}
+
+impl std::fmt::Display for CombatEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}s] {} -> {}: ",
+            self.time as f64 / 1000.0,
+            self.source.id(),
+            self.target.id()
+        )?;
+        match &self.kind {
+            CombatEventKind::ActionUsed { name, .. } => write!(f, "uses {}", name),
+            CombatEventKind::DamageDealt {
+                amount,
+                critical_hit,
+                direct_hit,
+                buffs,
+                ..
+            } => write!(
+                f,
+                "deals {} damage{}{}{}",
+                amount,
+                if *critical_hit { " (crit)" } else { "" },
+                if *direct_hit { " (direct hit)" } else { "" },
+                buffed_by(buffs)
+            ),
+            CombatEventKind::DamageOverTimeTicked {
+                amount,
+                critical_hit,
+                direct_hit,
+                buffs,
+                ..
+            } => write!(
+                f,
+                "deals {} damage over time{}{}{}",
+                amount,
+                if *critical_hit { " (crit)" } else { "" },
+                if *direct_hit { " (direct hit)" } else { "" },
+                buffed_by(buffs)
+            ),
+            CombatEventKind::StatusApplied { name, .. } => write!(f, "applies {}", name),
+            CombatEventKind::StatusRefreshed { name, .. } => write!(f, "refreshes {}", name),
+            CombatEventKind::StatusExpired { name } => write!(f, "{} expires", name),
+            CombatEventKind::ComboSet { action_id } => write!(f, "combo from {}", action_id),
+            CombatEventKind::ComboConsumed { action_id } => {
+                write!(f, "consumes combo from {}", action_id)
+            }
+            CombatEventKind::GcdStarted { duration } => {
+                write!(f, "starts a {}s GCD", *duration as f64 / 1000.0)
+            }
+            CombatEventKind::ScriptStepLate {
+                action_id,
+                scheduled,
+                ..
+            } => match scheduled {
+                Some(scheduled) => write!(
+                    f,
+                    "scripted {} is not ready at {}s",
+                    action_id,
+                    *scheduled as f64 / 1000.0
+                ),
+                None => write!(f, "scripted {} is not ready", action_id),
+            },
+            CombatEventKind::ComboMissed { action_id } => {
+                write!(f, "misses combo from {}", action_id)
+            }
+            CombatEventKind::CastStarted {
+                action_id,
+                duration,
+            } => write!(
+                f,
+                "starts casting {} for {}s",
+                action_id,
+                *duration as f64 / 1000.0
+            ),
+            CombatEventKind::CastCompleted { action_id } => {
+                write!(f, "finishes casting {}", action_id)
+            }
+            CombatEventKind::AnimationLockStarted { duration } => {
+                write!(f, "is locked for {}s", *duration as f64 / 1000.0)
+            }
+        }
+    }
+}
+
+// buffed_by lists the statuses that affected a hit, if any.
+fn buffed_by(buffs: &[String]) -> String {
+    if buffs.is_empty() {
+        return String::new();
+    }
+    format!(" (buffed by {})", buffs.join(", "))
+}
+
+/// CombatLog is the ECS resource every system and result appends its events to, in the order
+/// they happened.
+#[derive(Default, Debug, Clone)]
+pub struct CombatLog(Vec<CombatEvent>);
+
+impl CombatLog {
+    pub fn record(&mut self, time: SimTime, source: Entity, target: Entity, kind: CombatEventKind) {
+        self.0.push(CombatEvent {
+            time,
+            source,
+            target,
+            kind,
+        });
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, CombatEvent> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn into_events(self) -> Vec<CombatEvent> {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record() {
+        let mut log = CombatLog::default();
+        assert!(log.is_empty());
+        log.record(
+            10,
+            Entity::new(1),
+            Entity::new(2),
+            CombatEventKind::ComboSet { action_id: 1 },
+        );
+        assert_eq!(1, log.len());
+        let event = log.iter().next().unwrap();
+        assert_eq!(10, event.time);
+        assert_eq!(Entity::new(1), event.source);
+        assert_eq!(Entity::new(2), event.target);
+        assert_eq!(CombatEventKind::ComboSet { action_id: 1 }, event.kind);
+    }
+
+    #[test]
+    fn display() {
+        let event = CombatEvent {
+            time: 2500,
+            source: Entity::new(1),
+            target: Entity::new(2),
+            kind: CombatEventKind::DamageDealt {
+                action_id: 1,
+                potency: 290,
+                amount: 12345,
+                critical_hit: true,
+                direct_hit: false,
+                multipliers: vec![],
+                buffs: vec![],
+            },
+        };
+        assert_eq!(
+            "[2.5s] 1 -> 2: deals 12345 damage (crit)",
+            event.to_string()
+        );
+    }
+
+    #[test]
+    fn display_buffed_damage() {
+        let event = CombatEvent {
+            time: 2500,
+            source: Entity::new(1),
+            target: Entity::new(2),
+            kind: CombatEventKind::DamageDealt {
+                action_id: 1,
+                potency: 290,
+                amount: 12345,
+                critical_hit: false,
+                direct_hit: false,
+                multipliers: vec![1.15, 1.1],
+                buffs: vec!["Lance Charge".into(), "Disembowel".into()],
+            },
+        };
+        assert_eq!(
+            "[2.5s] 1 -> 2: deals 12345 damage (buffed by Lance Charge, Disembowel)",
+            event.to_string()
+        );
+    }
+}
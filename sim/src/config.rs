@@ -0,0 +1,338 @@
+use crate::actor::action::Actions;
+use crate::actor::calc::lookup::Job;
+use crate::actor::rotation::dsl::{self, ParseError};
+use crate::actor::rotation::{CheckCombo, Rotation, RotationEntry};
+use crate::actor::stat::{Stat, Stats};
+use crate::jobs;
+use crate::registry;
+use crate::simulation::ActorConfig;
+use crate::xivapi;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// ConfigError is why an actor could not be loaded from its action, stats and rotation files.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    // Parse names the file, or built in data, that failed to parse.
+    Parse(String, serde_json::Error),
+    // MissingData is a file the job has no built in data for.
+    MissingData(Job, &'static str),
+    UnknownAction(String),
+    // OverlayWithActions is an overlay given along with action definitions, which it can't apply to.
+    OverlayWithActions,
+    // Rotation names the rotation file, or built in rotation, that failed to parse.
+    Rotation(String, ParseError),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            ConfigError::Parse(name, err) => write!(f, "{}: {}", name, err),
+            ConfigError::MissingData(job, kind) => write!(
+                f,
+                "{:?} has no built in {}; pass a {} file",
+                job, kind, kind
+            ),
+            ConfigError::UnknownAction(name) => write!(f, "Unknown action: {}", name),
+            ConfigError::OverlayWithActions => write!(
+                f,
+                "Overlays only apply to built in actions; pass an overlay or an actions file, not both"
+            ),
+            ConfigError::Rotation(name, err) => write!(f, "{}: {}", name, err),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// RotationFileEntry is one line of a rotation file, in priority order, eg.
+/// `{"action": "Vorpal Thrust", "combo": "True Thrust"}`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RotationFileEntry {
+    pub action: String,
+    // combo only allows the action while the named action's combo is active.
+    pub combo: Option<String>,
+    // condition only allows the action while the condition holds, written as in a rotation file,
+    // eg. `has_status(Power Surge) || !combo(True Thrust)`.
+    pub condition: Option<String>,
+}
+
+/// ActorFiles are the files an actor is loaded from. Missing files fall back to the job's built in
+/// data.
+#[derive(Default, Clone, Copy)]
+pub struct ActorFiles<'a> {
+    // actions replaces the job's xivapi records with hand written action definitions.
+    pub actions: Option<&'a Path>,
+    // overlay replaces the job's built in potencies and effects for its xivapi records.
+    pub overlay: Option<&'a Path>,
+    pub stats: Option<&'a Path>,
+    pub rotation: Option<&'a Path>,
+}
+
+pub fn load_actor(job: Job, files: ActorFiles) -> Result<ActorConfig, ConfigError> {
+    let built_in = jobs::built_in(job);
+    let load = |kind: &'static str, path: Option<&Path>, built_in: Option<&'static str>| match path
+    {
+        Some(path) => Ok((path.display().to_string(), read(path)?)),
+        None => built_in
+            .map(|source| (format!("built in {:?} {}", job, kind), source.to_string()))
+            .ok_or(ConfigError::MissingData(job, kind)),
+    };
+
+    let actions = match (files.actions, &built_in) {
+        (Some(_), _) if files.overlay.is_some() => return Err(ConfigError::OverlayWithActions),
+        (None, Some(data)) => {
+            let (name, overlay) = match load("overlay", files.overlay, data.overlay) {
+                Err(ConfigError::MissingData(..)) => (None, "{}".to_string()),
+                loaded => loaded.map(|(name, overlay)| (Some(name), overlay))?,
+            };
+            xivapi::import_actions(data.records, &overlay)
+                .map_err(|err| ConfigError::Parse(built_in_actions_name(job, name), err))?
+        }
+        (path, _) => {
+            let (name, source) = load("actions", path, None)?;
+            registry::parse_actions(&source).map_err(|err| ConfigError::Parse(name, err))?
+        }
+    };
+    let (name, source) = load(
+        "stats",
+        files.stats,
+        built_in.as_ref().and_then(|data| data.stats),
+    )?;
+    let stats = parse_stats(&source).map_err(|err| ConfigError::Parse(name, err))?;
+    let (name, source) = load(
+        "rotation",
+        files.rotation,
+        built_in.as_ref().and_then(|data| data.rotation),
+    )?;
+    // Rotation files are written in the rotation language, unless they are the older JSON lists.
+    let rotation = match files.rotation {
+        Some(path) if path.extension() == Some("json".as_ref()) => {
+            let entries: Vec<RotationFileEntry> =
+                serde_json::from_str(&source).map_err(|err| ConfigError::Parse(name, err))?;
+            build_rotation(&entries, &actions)?
+        }
+        _ => dsl::parse(&source, &actions).map_err(|err| ConfigError::Rotation(name, err))?,
+    };
+    Ok(ActorConfig {
+        job,
+        actions,
+        rotation,
+        stats,
+    })
+}
+
+// built_in_actions_name names the built in actions, and the overlay applied to them if any.
+fn built_in_actions_name(job: Job, overlay: Option<String>) -> String {
+    match overlay {
+        Some(overlay) => format!("built in {:?} actions with {}", job, overlay),
+        None => format!("built in {:?} actions", job),
+    }
+}
+
+fn read(path: &Path) -> Result<String, ConfigError> {
+    std::fs::read_to_string(path).map_err(|err| ConfigError::Io(path.into(), err))
+}
+
+// parse_stats reads base stats from a JSON object of stat names to values, eg.
+// `{"Strength": 5435, "CriticalHitRate": 3543}`.
+pub fn parse_stats(source: &str) -> Result<Stats, serde_json::Error> {
+    let values: HashMap<Stat, i64> = serde_json::from_str(source)?;
+    let mut stats = Stats::default();
+    for (stat, amount) in values {
+        stats.set_base(stat, amount);
+    }
+    Ok(stats)
+}
+
+pub fn build_rotation(
+    entries: &[RotationFileEntry],
+    actions: &Actions,
+) -> Result<Rotation, ConfigError> {
+    let find = |name: &str| {
+        actions
+            .find(name)
+            .ok_or_else(|| ConfigError::UnknownAction(name.into()))
+    };
+    let mut rotation = Rotation::default();
+    for entry in entries {
+        let mut rotation_entry = RotationEntry::new(find(&entry.action)?);
+        if let Some(combo) = &entry.combo {
+            rotation_entry = rotation_entry.with_condition(Arc::new(CheckCombo(find(combo)?.id)));
+        }
+        if let Some(condition) = &entry.condition {
+            let action = find(&entry.action)?;
+            let conditions = dsl::parse_conditions(condition, action, actions).map_err(|err| {
+                ConfigError::Rotation(format!("condition of {}", entry.action), err)
+            })?;
+            for condition in conditions {
+                rotation_entry = rotation_entry.with_condition(condition);
+            }
+        }
+        rotation.add(rotation_entry);
+    }
+    Ok(rotation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::rotation::ActorState;
+
+    fn drg_actions() -> Actions {
+        let data = jobs::built_in(Job::DRG).unwrap();
+        xivapi::import_actions(data.records, data.overlay.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn job_from_str() {
+        assert!(matches!("drg".parse::<Job>(), Ok(Job::DRG)));
+        assert!(matches!("PLD".parse::<Job>(), Ok(Job::PLD)));
+        assert!("None".parse::<Job>().is_err());
+    }
+
+    #[test]
+    fn stats() {
+        let stats = parse_stats(r#"{"Strength": 5435, "CriticalHitRate": 3543}"#).unwrap();
+        assert_eq!(5435, stats.get(Stat::Strength));
+        assert_eq!(3543, stats.get(Stat::CriticalHitRate));
+        assert_eq!(0, stats.get(Stat::Determination));
+        assert!(parse_stats(r#"{"Strenght": 5435}"#).is_err());
+    }
+
+    #[test]
+    fn rotation() {
+        let actions = drg_actions();
+        let entries: Vec<RotationFileEntry> = serde_json::from_str(
+            r#"[{"action": "Vorpal Thrust", "combo": "True Thrust"}, {"action": "True Thrust"}]"#,
+        )
+        .unwrap();
+        let rotation = build_rotation(&entries, &actions).unwrap();
+        let mut actor = ActorState::default();
+        assert_eq!(Some(75), rotation.get_next_action_id(&actor.view(0)));
+        actor.active_combos.add_action(75);
+        assert_eq!(Some(78), rotation.get_next_action_id(&actor.view(0)));
+    }
+
+    #[test]
+    fn rotation_condition() {
+        let actions = drg_actions();
+        let entries: Vec<RotationFileEntry> = serde_json::from_str(
+            r#"[{"action": "Vorpal Thrust", "condition": "combo(True Thrust) || gauge(Eyes) > 0"}, {"action": "True Thrust"}]"#,
+        )
+        .unwrap();
+        let rotation = build_rotation(&entries, &actions).unwrap();
+        let mut actor = ActorState::default();
+        assert_eq!(Some(75), rotation.get_next_action_id(&actor.view(0)));
+        actor.job_gauge.set("Eyes", 1);
+        assert_eq!(Some(78), rotation.get_next_action_id(&actor.view(0)));
+
+        let entries: Vec<RotationFileEntry> =
+            serde_json::from_str(r#"[{"action": "True Thrust", "condition": "combo("}]"#).unwrap();
+        assert_eq!(
+            "condition of True Thrust: line 1, column 7: expected `)`",
+            build_rotation(&entries, &actions)
+                .err()
+                .unwrap()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn rotation_unknown_action() {
+        let actions = drg_actions();
+        let entries = vec![RotationFileEntry {
+            action: "Raiden Thrust".into(),
+            combo: None,
+            condition: None,
+        }];
+        assert!(matches!(
+            build_rotation(&entries, &actions),
+            Err(ConfigError::UnknownAction(name)) if name == "Raiden Thrust"
+        ));
+    }
+
+    #[test]
+    fn load_actor_built_in() {
+        let actor = load_actor(Job::DRG, ActorFiles::default()).unwrap();
+        assert_eq!("Life Surge", actor.actions.get(&83).unwrap().name);
+        assert_eq!(5435, actor.stats.get(Stat::Strength));
+        let state = ActorState::default();
+        assert_eq!(Some(83), actor.rotation.get_next_action_id(&state.view(0)));
+    }
+
+    #[test]
+    fn load_actor_rotation_file() {
+        let path = std::env::temp_dir().join("ffxivsim_load_actor_rotation_file.rotation");
+        std::fs::write(
+            &path,
+            "use True Thrust\nuse Vorpal Thrust if combo(Raiden Thrust)\n",
+        )
+        .unwrap();
+        let err = load_actor(
+            Job::DRG,
+            ActorFiles {
+                rotation: Some(&path),
+                ..Default::default()
+            },
+        )
+        .err()
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            format!(
+                "{}: line 2, column 28: unknown action `Raiden Thrust`",
+                path.display()
+            ),
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn load_actor_missing_stats() {
+        assert!(matches!(
+            load_actor(Job::PLD, ActorFiles::default()),
+            Err(ConfigError::MissingData(Job::PLD, "stats"))
+        ));
+    }
+
+    #[test]
+    fn load_actor_overlay_with_actions() {
+        assert!(matches!(
+            load_actor(
+                Job::DRG,
+                ActorFiles {
+                    actions: Some(Path::new("actions.json")),
+                    overlay: Some(Path::new("overlay.json")),
+                    ..Default::default()
+                }
+            ),
+            Err(ConfigError::OverlayWithActions)
+        ));
+    }
+
+    #[test]
+    fn built_in_actions_name_without_overlay() {
+        assert_eq!(
+            "built in PLD actions",
+            built_in_actions_name(Job::PLD, None)
+        );
+        assert_eq!(
+            "built in DRG actions with built in DRG overlay",
+            built_in_actions_name(Job::DRG, Some("built in DRG overlay".into()))
+        );
+    }
+
+    #[test]
+    fn load_actor_unsupported_job() {
+        assert!(matches!(
+            load_actor(Job::WHM, ActorFiles::default()),
+            Err(ConfigError::MissingData(Job::WHM, "actions"))
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::sim::SimTime;
+use serde::Serialize;
+
+pub const DEFAULT_DURATION: SimTime = 15000;
+
+/// Encounter describes how long a fight lasts and what ends it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Encounter {
+    // Duration runs the fight for a fixed amount of time.
+    Duration(SimTime),
+    // TargetHp ends the fight once the target has taken `hp` damage, or at `time_limit`.
+    TargetHp {
+        hp: i64,
+        time_limit: SimTime,
+    },
+    // KillTime ends the fight once the target has taken `hp` damage, but never before `earliest`
+    // (eg. a scripted phase) and never after `latest` (eg. an enrage).
+    KillTime {
+        hp: i64,
+        earliest: SimTime,
+        latest: SimTime,
+    },
+}
+
+impl Default for Encounter {
+    fn default() -> Self {
+        Encounter::Duration(DEFAULT_DURATION)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum EndReason {
+    DurationElapsed,
+    TargetKilled,
+    Enrage,
+}
+
+impl std::fmt::Display for EndReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EndReason::DurationElapsed => write!(f, "duration elapsed"),
+            EndReason::TargetKilled => write!(f, "target killed"),
+            EndReason::Enrage => write!(f, "enrage"),
+        }
+    }
+}
+
+impl Encounter {
+    // time_limit is the latest timestamp the fight can last until.
+    pub fn time_limit(&self) -> SimTime {
+        match self {
+            Encounter::Duration(duration) => *duration,
+            Encounter::TargetHp { time_limit, .. } => *time_limit,
+            Encounter::KillTime { latest, .. } => *latest,
+        }
+    }
+
+    pub fn time_limit_reason(&self) -> EndReason {
+        match self {
+            Encounter::Duration(_) => EndReason::DurationElapsed,
+            _ => EndReason::Enrage,
+        }
+    }
+
+    pub fn target_hp(&self) -> Option<i64> {
+        match self {
+            Encounter::Duration(_) => None,
+            Encounter::TargetHp { hp, .. } | Encounter::KillTime { hp, .. } => Some(*hp),
+        }
+    }
+
+    // earliest_kill is the first timestamp at which the target is allowed to die.
+    pub fn earliest_kill(&self) -> SimTime {
+        match self {
+            Encounter::KillTime { earliest, .. } => *earliest,
+            _ => 0,
+        }
+    }
+
+    pub fn is_target_dead(&self, damage: i64) -> bool {
+        match self.target_hp() {
+            Some(hp) => damage >= hp,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_limit() {
+        assert_eq!(1000, Encounter::Duration(1000).time_limit());
+        assert_eq!(
+            2000,
+            Encounter::TargetHp {
+                hp: 10,
+                time_limit: 2000
+            }
+            .time_limit()
+        );
+        assert_eq!(
+            3000,
+            Encounter::KillTime {
+                hp: 10,
+                earliest: 1000,
+                latest: 3000
+            }
+            .time_limit()
+        );
+    }
+
+    #[test]
+    fn time_limit_reason() {
+        assert_eq!(
+            EndReason::DurationElapsed,
+            Encounter::Duration(1000).time_limit_reason()
+        );
+        assert_eq!(
+            EndReason::Enrage,
+            Encounter::TargetHp {
+                hp: 10,
+                time_limit: 2000
+            }
+            .time_limit_reason()
+        );
+    }
+
+    #[test]
+    fn is_target_dead() {
+        let encounter = Encounter::TargetHp {
+            hp: 100,
+            time_limit: 1000,
+        };
+        assert!(!encounter.is_target_dead(99));
+        assert!(encounter.is_target_dead(100));
+        assert!(!Encounter::Duration(1000).is_target_dead(i64::MAX));
+    }
+}
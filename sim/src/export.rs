@@ -0,0 +1,501 @@
+use crate::actor::auto_attack::{AUTO_ATTACK_ID, AUTO_ATTACK_NAME};
+use crate::combat_log::{CombatEvent, CombatEventKind};
+use crate::sim::SimTime;
+use crate::simulation::SimulationResult;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// ActionDamage is one row of the per-action damage breakdown.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActionDamage {
+    pub action_id: u32,
+    pub name: String,
+    pub hits: u64,
+    pub total_damage: i64,
+    pub average_hit: f64,
+    pub critical_hit_rate: f64,
+    pub direct_hit_rate: f64,
+    // percent is this action's share of the run's total damage.
+    pub percent: f64,
+}
+
+// action_breakdown lists the damage each action dealt to the target, highest total first. Names
+// are taken from the ActionUsed events in the log.
+pub fn action_breakdown(result: &SimulationResult) -> Vec<ActionDamage> {
+    let names: HashMap<u32, &str> = result
+        .events
+        .iter()
+        .filter_map(|event| match &event.kind {
+            CombatEventKind::ActionUsed { action_id, name } => Some((*action_id, name.as_str())),
+            _ => None,
+        })
+        // Auto-attacks aren't used like actions, so they never show up as ActionUsed.
+        .chain(std::iter::once((AUTO_ATTACK_ID, AUTO_ATTACK_NAME)))
+        .collect();
+    let mut breakdown: Vec<ActionDamage> = result
+        .damage
+        .actions()
+        .map(|(action_id, tally)| ActionDamage {
+            action_id: *action_id,
+            name: names.get(action_id).copied().unwrap_or_default().into(),
+            hits: tally.hits,
+            total_damage: tally.total,
+            average_hit: tally.average_hit(),
+            critical_hit_rate: tally.critical_hit_rate(),
+            direct_hit_rate: tally.direct_hit_rate(),
+            percent: tally.share_of(result.total_damage),
+        })
+        .collect();
+    breakdown.sort_by(|a, b| {
+        b.total_damage
+            .cmp(&a.total_damage)
+            .then(a.action_id.cmp(&b.action_id))
+    });
+    breakdown
+}
+
+#[derive(Serialize)]
+struct JsonExport<'a> {
+    #[serde(flatten)]
+    result: &'a SimulationResult,
+    dps: f64,
+    actions: Vec<ActionDamage>,
+}
+
+/// Writes the totals, per-action breakdown and full event log of a run as a single JSON document.
+pub fn write_json<W: Write>(result: &SimulationResult, writer: W) -> serde_json::Result<()> {
+    serde_json::to_writer_pretty(
+        writer,
+        &JsonExport {
+            result,
+            dps: result.dps(),
+            actions: action_breakdown(result),
+        },
+    )
+}
+
+const EVENT_COLUMNS: [&str; 16] = [
+    "time",
+    "source",
+    "target",
+    "type",
+    "action_id",
+    "name",
+    "potency",
+    "amount",
+    "critical_hit",
+    "direct_hit",
+    "multipliers",
+    "buffs",
+    "expiration",
+    "duration",
+    "scheduled",
+    "ready_at",
+];
+
+// joined lists multipliers in a single column, separated by semicolons.
+fn joined(multipliers: &[f64]) -> String {
+    multipliers
+        .iter()
+        .map(|multiplier| multiplier.to_string())
+        .collect::<Vec<String>>()
+        .join(";")
+}
+
+// EventRow is an event flattened into EVENT_COLUMNS. Columns that don't apply are left empty.
+#[derive(Default, Serialize)]
+struct EventRow {
+    time: SimTime,
+    source: u32,
+    target: u32,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    action_id: Option<u32>,
+    name: Option<String>,
+    potency: Option<i64>,
+    amount: Option<i64>,
+    critical_hit: Option<bool>,
+    direct_hit: Option<bool>,
+    multipliers: Option<String>,
+    buffs: Option<String>,
+    expiration: Option<SimTime>,
+    duration: Option<SimTime>,
+    scheduled: Option<SimTime>,
+    ready_at: Option<SimTime>,
+}
+
+impl EventRow {
+    fn new(event: &CombatEvent) -> Self {
+        let row = EventRow {
+            time: event.time,
+            source: event.source.id(),
+            target: event.target.id(),
+            ..Default::default()
+        };
+        match &event.kind {
+            CombatEventKind::ActionUsed { action_id, name } => EventRow {
+                kind: "ActionUsed",
+                action_id: Some(*action_id),
+                name: Some(name.clone()),
+                ..row
+            },
+            CombatEventKind::DamageDealt {
+                action_id,
+                potency,
+                amount,
+                critical_hit,
+                direct_hit,
+                multipliers,
+                buffs,
+            } => EventRow {
+                kind: "DamageDealt",
+                action_id: Some(*action_id),
+                potency: Some(*potency),
+                amount: Some(*amount),
+                critical_hit: Some(*critical_hit),
+                direct_hit: Some(*direct_hit),
+                multipliers: Some(joined(multipliers)),
+                buffs: Some(buffs.join(";")),
+                ..row
+            },
+            CombatEventKind::DamageOverTimeTicked {
+                action_id,
+                potency,
+                amount,
+                critical_hit,
+                direct_hit,
+                multipliers,
+                buffs,
+            } => EventRow {
+                kind: "DamageOverTimeTicked",
+                action_id: Some(*action_id),
+                potency: Some(*potency),
+                amount: Some(*amount),
+                critical_hit: Some(*critical_hit),
+                direct_hit: Some(*direct_hit),
+                multipliers: Some(joined(multipliers)),
+                buffs: Some(buffs.join(";")),
+                ..row
+            },
+            CombatEventKind::StatusApplied { name, expiration } => EventRow {
+                kind: "StatusApplied",
+                name: Some(name.clone()),
+                expiration: Some(*expiration),
+                ..row
+            },
+            CombatEventKind::StatusRefreshed { name, expiration } => EventRow {
+                kind: "StatusRefreshed",
+                name: Some(name.clone()),
+                expiration: Some(*expiration),
+                ..row
+            },
+            CombatEventKind::StatusExpired { name } => EventRow {
+                kind: "StatusExpired",
+                name: Some(name.clone()),
+                ..row
+            },
+            CombatEventKind::ComboSet { action_id } => EventRow {
+                kind: "ComboSet",
+                action_id: Some(*action_id),
+                ..row
+            },
+            CombatEventKind::ComboConsumed { action_id } => EventRow {
+                kind: "ComboConsumed",
+                action_id: Some(*action_id),
+                ..row
+            },
+            CombatEventKind::GcdStarted { duration } => EventRow {
+                kind: "GcdStarted",
+                duration: Some(*duration),
+                ..row
+            },
+            CombatEventKind::ScriptStepLate {
+                action_id,
+                scheduled,
+                ready_at,
+            } => EventRow {
+                kind: "ScriptStepLate",
+                action_id: Some(*action_id),
+                scheduled: *scheduled,
+                ready_at: Some(*ready_at),
+                ..row
+            },
+            CombatEventKind::ComboMissed { action_id } => EventRow {
+                kind: "ComboMissed",
+                action_id: Some(*action_id),
+                ..row
+            },
+            CombatEventKind::CastStarted {
+                action_id,
+                duration,
+            } => EventRow {
+                kind: "CastStarted",
+                action_id: Some(*action_id),
+                duration: Some(*duration),
+                ..row
+            },
+            CombatEventKind::CastCompleted { action_id } => EventRow {
+                kind: "CastCompleted",
+                action_id: Some(*action_id),
+                ..row
+            },
+            CombatEventKind::AnimationLockStarted { duration } => EventRow {
+                kind: "AnimationLockStarted",
+                duration: Some(*duration),
+                ..row
+            },
+        }
+    }
+}
+
+/// Writes the event log of a run as CSV, one event per row.
+pub fn write_events_csv<W: Write>(result: &SimulationResult, writer: W) -> csv::Result<()> {
+    // The header is written up front so logs without events still get one.
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(writer);
+    writer.write_record(EVENT_COLUMNS)?;
+    for event in &result.events {
+        writer.serialize(EventRow::new(event))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+const BREAKDOWN_COLUMNS: [&str; 8] = [
+    "action_id",
+    "name",
+    "hits",
+    "total_damage",
+    "average_hit",
+    "critical_hit_rate",
+    "direct_hit_rate",
+    "percent",
+];
+
+// BreakdownRow is an ActionDamage as written to CSV, where the Total row has no action id.
+#[derive(Serialize)]
+struct BreakdownRow {
+    action_id: Option<u32>,
+    name: String,
+    hits: u64,
+    total_damage: i64,
+    average_hit: f64,
+    critical_hit_rate: f64,
+    direct_hit_rate: f64,
+    percent: f64,
+}
+
+impl From<ActionDamage> for BreakdownRow {
+    fn from(damage: ActionDamage) -> Self {
+        BreakdownRow {
+            action_id: Some(damage.action_id),
+            name: damage.name,
+            hits: damage.hits,
+            total_damage: damage.total_damage,
+            average_hit: damage.average_hit,
+            critical_hit_rate: damage.critical_hit_rate,
+            direct_hit_rate: damage.direct_hit_rate,
+            percent: damage.percent,
+        }
+    }
+}
+
+/// Writes the per-action damage breakdown of a run as CSV, followed by a row with the totals.
+pub fn write_breakdown_csv<W: Write>(result: &SimulationResult, writer: W) -> csv::Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(writer);
+    writer.write_record(BREAKDOWN_COLUMNS)?;
+    let breakdown = action_breakdown(result);
+    let percent = breakdown
+        .iter()
+        .fold(0.0, |percent, row| percent + row.percent);
+    for row in breakdown {
+        writer.serialize(BreakdownRow::from(row))?;
+    }
+    let tally = result.damage.tally();
+    writer.serialize(BreakdownRow {
+        action_id: None,
+        name: "Total".into(),
+        hits: tally.hits,
+        total_damage: tally.total,
+        average_hit: tally.average_hit(),
+        critical_hit_rate: tally.critical_hit_rate(),
+        direct_hit_rate: tally.direct_hit_rate(),
+        percent,
+    })?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::damage::{Damage, HitType};
+    use crate::encounter::EndReason;
+    use bevy_ecs::prelude::Entity;
+
+    fn damage_dealt(time: u64, action_id: u32, amount: i64) -> CombatEvent {
+        CombatEvent {
+            time,
+            source: Entity::new(1),
+            target: Entity::new(2),
+            kind: CombatEventKind::DamageDealt {
+                action_id,
+                potency: 100,
+                amount,
+                critical_hit: false,
+                direct_hit: true,
+                multipliers: vec![1.1, 1.05],
+                buffs: vec!["Lance Charge".into(), "Disembowel".into()],
+            },
+        }
+    }
+
+    fn action(time: u64, action_id: u32, name: &str) -> CombatEvent {
+        CombatEvent {
+            time,
+            source: Entity::new(1),
+            target: Entity::new(2),
+            kind: CombatEventKind::ActionUsed {
+                action_id,
+                name: name.into(),
+            },
+        }
+    }
+
+    fn result() -> SimulationResult {
+        let mut damage = Damage::default();
+        damage.add(Entity::new(1), 1, 100, HitType::DirectHit);
+        damage.add(Entity::new(1), 2, 200, HitType::DirectHit);
+        damage.add(Entity::new(1), 1, 100, HitType::DirectHit);
+        SimulationResult {
+            duration: 5000,
+            end_reason: EndReason::DurationElapsed,
+            seed: Some(1),
+            total_damage: 400,
+            damage,
+            events: vec![
+                action(0, 1, "True Thrust"),
+                damage_dealt(0, 1, 100),
+                action(2500, 2, "Vorpal Thrust"),
+                damage_dealt(2500, 2, 200),
+                action(5000, 1, "True Thrust"),
+                damage_dealt(5000, 1, 100),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_action_breakdown() {
+        assert_eq!(
+            vec![
+                ActionDamage {
+                    action_id: 1,
+                    name: "True Thrust".into(),
+                    hits: 2,
+                    total_damage: 200,
+                    average_hit: 100.0,
+                    critical_hit_rate: 0.0,
+                    direct_hit_rate: 1.0,
+                    percent: 50.0,
+                },
+                ActionDamage {
+                    action_id: 2,
+                    name: "Vorpal Thrust".into(),
+                    hits: 1,
+                    total_damage: 200,
+                    average_hit: 200.0,
+                    critical_hit_rate: 0.0,
+                    direct_hit_rate: 1.0,
+                    percent: 50.0,
+                },
+            ],
+            action_breakdown(&result())
+        );
+    }
+
+    #[test]
+    fn test_write_json() {
+        let mut buffer = Vec::new();
+        write_json(&result(), &mut buffer).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        assert_eq!(400, json["total_damage"]);
+        assert_eq!(80.0, json["dps"]);
+        assert_eq!("DurationElapsed", json["end_reason"]);
+        assert_eq!(2, json["actions"].as_array().unwrap().len());
+        assert_eq!("DamageDealt", json["events"][1]["type"]);
+        assert_eq!(1, json["events"][1]["source"]);
+        assert_eq!(200, json["events"][3]["amount"]);
+    }
+
+    #[test]
+    fn test_write_events_csv() {
+        let mut buffer = Vec::new();
+        write_events_csv(&result(), &mut buffer).unwrap();
+        let csv = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(7, lines.len());
+        assert_eq!(
+            "time,source,target,type,action_id,name,potency,amount,critical_hit,direct_hit,multipliers,buffs,expiration,duration,scheduled,ready_at",
+            lines[0]
+        );
+        assert_eq!("0,1,2,ActionUsed,1,True Thrust,,,,,,,,,,", lines[1]);
+        assert_eq!(
+            "0,1,2,DamageDealt,1,,100,100,false,true,1.1;1.05,Lance Charge;Disembowel,,,,",
+            lines[2]
+        );
+    }
+
+    #[test]
+    fn test_write_breakdown_csv() {
+        let mut buffer = Vec::new();
+        write_breakdown_csv(&result(), &mut buffer).unwrap();
+        let csv = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            "action_id,name,hits,total_damage,average_hit,critical_hit_rate,direct_hit_rate,percent\n\
+             1,True Thrust,2,200,100.0,0.0,1.0,50.0\n\
+             2,Vorpal Thrust,1,200,200.0,0.0,1.0,50.0\n\
+             ,Total,3,400,133.33333333333334,0.0,1.0,100.0\n",
+            csv
+        );
+    }
+
+    #[test]
+    fn test_write_breakdown_csv_without_damage() {
+        let result = SimulationResult {
+            total_damage: 0,
+            damage: Damage::default(),
+            events: vec![],
+            ..result()
+        };
+        let mut buffer = Vec::new();
+        write_breakdown_csv(&result, &mut buffer).unwrap();
+        let csv = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            "action_id,name,hits,total_damage,average_hit,critical_hit_rate,direct_hit_rate,percent\n\
+             ,Total,0,0,0.0,0.0,0.0,0.0\n",
+            csv
+        );
+    }
+
+    #[test]
+    fn columns_match_rows() {
+        // Rows serialize their own header, which has to line up with the one written up front.
+        fn header<T: Serialize>(row: T) -> String {
+            let mut writer = csv::Writer::from_writer(vec![]);
+            writer.serialize(row).unwrap();
+            let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+            csv.lines().next().unwrap().to_string()
+        }
+        assert_eq!(
+            EVENT_COLUMNS.join(","),
+            header(EventRow::new(&action(0, 1, "True Thrust")))
+        );
+        for row in action_breakdown(&result()) {
+            assert_eq!(BREAKDOWN_COLUMNS.join(","), header(BreakdownRow::from(row)));
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use crate::actor::calc::lookup::Job;
+
+/// JobData is the built in data of a job, in the same formats as the files the CLI loads.
+pub struct JobData {
+    // records is the xivapi dump of the job's actions that the app ships.
+    pub records: &'static str,
+    // overlay is a JSON object of xivapi::ActionOverlays keyed by action name.
+    pub overlay: Option<&'static str>,
+    // rotation is an example rotation, in the language parsed by rotation::dsl.
+    pub rotation: Option<&'static str>,
+    // stats is an example JSON object of base stats.
+    pub stats: Option<&'static str>,
+}
+
+// built_in is the data shipped for a job, or None if there is no xivapi dump for it yet.
+pub fn built_in(job: Job) -> Option<JobData> {
+    match job {
+        Job::DRG => Some(JobData {
+            records: include_str!("../../app/data/drg.json"),
+            overlay: Some(include_str!("../data/overlays/drg.json")),
+            rotation: Some(include_str!("../data/rotations/drg.rotation")),
+            stats: Some(include_str!("../data/stats/drg.json")),
+        }),
+        Job::PLD => Some(JobData {
+            records: include_str!("../../app/data/PLD.json"),
+            overlay: None,
+            rotation: None,
+            stats: None,
+        }),
+        Job::GNB => Some(JobData {
+            records: include_str!("../../app/data/gnb.json"),
+            overlay: None,
+            rotation: None,
+            stats: None,
+        }),
+        _ => None,
+    }
+}
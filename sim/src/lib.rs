@@ -0,0 +1,21 @@
+// Job and level modifier names mirror the in-game abbreviations.
+#![allow(clippy::upper_case_acronyms)]
+// The original tests compare booleans with assert_eq!, and are kept as written.
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
+
+pub mod actor;
+pub mod batch;
+pub mod combat_log;
+pub mod config;
+pub mod encounter;
+pub mod export;
+pub mod jobs;
+pub mod registry;
+pub mod sim;
+pub mod simulation;
+mod systems;
+pub mod validate;
+pub mod xivapi;
+
+pub use batch::{Batch, BatchResult};
+pub use simulation::{ActorConfig, Simulation, SimulationResult};
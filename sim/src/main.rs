@@ -1,287 +1,367 @@
-mod actor;
-mod sim;
+use clap::{ArgEnum, Args, Parser, Subcommand};
+use ffxivsim::actor::calc::lookup::Job;
+use ffxivsim::config::{load_actor, ActorFiles, ConfigError};
+use ffxivsim::export;
+use ffxivsim::sim::{DamageMode, Ruleset, SimTime};
+use ffxivsim::validate::validate;
+use ffxivsim::{ActorConfig, Batch, Simulation};
+use serde_json::json;
+use std::error::Error;
+use std::io::{stdout, Write};
+use std::path::PathBuf;
 
-use actor::action::{Action, Actions};
-use actor::active_combos::ActiveCombos;
-use actor::apply::{Apply, ApplyCombo, DoDirectDamage, GiveStatusEffect, StartGcd, StartRecast};
-use actor::calc::lookup::Job;
-use actor::damage::Damage;
-use actor::recast_expirations::RecastExpirations;
-use actor::rotation::{CheckCombo, Rotation, RotationEntry};
-use actor::stat::{SpecialStat, Stat, Stats};
-use actor::status_effect::status;
-use actor::status_effect::status::{Status, StatusFlag, StatusFlags};
-use actor::status_effect::{StatusEffect, StatusEffects};
-use actor::{ActorTuple, QueryActor, Target};
-use bevy_app::{App, ScheduleRunnerPlugin, ScheduleRunnerSettings};
-use bevy_ecs::prelude::*;
-use bevy_utils::Duration;
-use sim::SimState;
-use std::sync::Arc;
-
-fn setup(mut commands: Commands) {
-    let mut actions = Actions::default();
-    let mut rotation = Rotation::default();
-
-    let life_surge = actor::Action {
-        id: 0,
-        name: "Life Surge".into(),
-        ogcd: true,
-        results: vec![
-            Arc::new(GiveStatusEffect {
-                status: Status {
-                    name: "Life Surge".into(),
-                    duration: 10000,
-                    flags: StatusFlags::new(&[StatusFlag::ExpireOnDirectDamage]),
-                    effects: vec![Arc::new(status::ModifySpecialStat {
-                        stat: SpecialStat::CriticalHitPercentOverride,
-                        amount: 100,
-                    })],
-                },
-                target_source: true,
-            }),
-            Arc::new(StartRecast {
-                // TODO: maybe id can be inferred
-                action_id: 0,
-                duration: 45000,
-            }),
-        ],
-        ..Default::default()
-    };
-    let true_thrust = actor::Action {
-        id: 1,
-        name: "True Thrust",
-        results: vec![
-            Arc::new(DoDirectDamage {
-                potency: 290,
-                ..Default::default()
-            }),
-            Arc::new(ApplyCombo(1)),
-            Arc::new(StartGcd::default()),
-        ],
-        ..Default::default()
-    };
-    let vorpal_thrust = actor::Action {
-        id: 2,
-        name: "Vorpal Thrust",
-        results: vec![
-            Arc::new(DoDirectDamage {
-                potency: 140,
-                combo_potency: Some(350),
-                combo_action_id: Some(1),
-                ..Default::default()
-            }),
-            Arc::new(StartGcd::default()),
-        ],
-        ..Default::default()
-    };
-    rotation.add(RotationEntry::new(&life_surge));
-    rotation.add(RotationEntry::new(&vorpal_thrust).with_condition(Arc::new(CheckCombo(1))));
-    rotation.add(RotationEntry::new(&true_thrust));
-    actions.add(life_surge);
-    actions.add(true_thrust);
-    actions.add(vorpal_thrust);
-
-    let mut stats = Stats::default();
-    stats.set_base(Stat::PhysicalWeaponDamage, 134);
-    stats.set_base(Stat::Strength, 5435);
-    stats.set_base(Stat::Dexterity, 326);
-    stats.set_base(Stat::Vitality, 6258);
-    stats.set_base(Stat::Intelligence, 206);
-    stats.set_base(Stat::Mind, 339);
-    stats.set_base(Stat::CriticalHitRate, 3543);
-    stats.set_base(Stat::Determination, 2965);
-    stats.set_base(Stat::DirectHitRate, 1620);
-    stats.set_base(Stat::Defense, 8740);
-    stats.set_base(Stat::MagicDefense, 8740);
-    stats.set_base(Stat::AttackPower, 5435);
-    stats.set_base(Stat::SkillSpeed, 1012);
-    stats.set_base(Stat::AttackMagicPotency, 206);
-    stats.set_base(Stat::HealingMagicPotency, 339);
-    stats.set_base(Stat::SpellSpeed, 380);
-    stats.set_base(Stat::Tenacity, 606);
-    stats.set_base(Stat::Piety, 340);
+#[derive(Parser)]
+#[clap(name = "ffxivsim", about = "XIV rotation simulator.")]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
 
-    commands.spawn().insert(SimState::default());
-    commands.spawn_bundle((
-        Job::DRG,
-        actions,
-        rotation,
-        RecastExpirations::default(),
-        Damage::default(),
-        StatusEffects::default(),
-        stats,
-        ActiveCombos::default(),
-    ));
-    commands.spawn_bundle((
-        Target::default(),
-        Job::None,
-        Actions::default(),
-        Rotation::default(),
-        RecastExpirations::default(),
-        Damage::default(),
-        StatusEffects::default(),
-        Stats::default(),
-        ActiveCombos::default(),
-    ));
+#[derive(Subcommand)]
+enum Command {
+    /// Simulate a single fight.
+    Run {
+        #[clap(flatten)]
+        actor: ActorArgs,
+        #[clap(flatten)]
+        sim: SimArgs,
+    },
+    /// Simulate many fights and summarize their DPS.
+    Batch {
+        #[clap(flatten)]
+        actor: ActorArgs,
+        #[clap(flatten)]
+        sim: SimArgs,
+        #[clap(flatten)]
+        batch: BatchArgs,
+    },
+    /// Simulate several stats or rotation files against each other with the same seeds.
+    Compare {
+        #[clap(long, default_value = "DRG")]
+        job: Job,
+        /// JSON list of action definitions shared by every candidate.
+        #[clap(long)]
+        actions: Option<PathBuf>,
+        /// JSON object of action overlays shared by every candidate.
+        #[clap(long)]
+        overlay: Option<PathBuf>,
+        /// Stats files to compare. A single file is shared by every candidate.
+        #[clap(long = "stats", multiple_occurrences = true)]
+        stats: Vec<PathBuf>,
+        /// Rotation files to compare. A single file is shared by every candidate.
+        #[clap(long = "rotation", multiple_occurrences = true)]
+        rotations: Vec<PathBuf>,
+        #[clap(flatten)]
+        sim: SimArgs,
+        #[clap(flatten)]
+        batch: BatchArgs,
+    },
+    /// Check that the action, stats and rotation files load, and that the rotation's scripted
+    /// steps keep to the recast, combo and animation lock rules.
+    Validate {
+        #[clap(flatten)]
+        actor: ActorArgs,
+        /// Output format; `json` prints violations as a list for tools.
+        #[clap(long, arg_enum, default_value_t = ValidateFormat::Text)]
+        format: ValidateFormat,
+        /// Milliseconds added to every action's animation lock, eg. for ping.
+        #[clap(long, default_value_t = 0)]
+        latency: SimTime,
+    },
 }
 
-fn tick(mut sim_state_query: Query<&mut SimState>) {
-    let mut sim_state = sim_state_query
-        .single_mut()
-        .expect("There should always be exactly one sim state.");
+#[derive(Args)]
+struct ActorArgs {
+    #[clap(long, default_value = "DRG")]
+    job: Job,
+    /// JSON list of action definitions. Defaults to the job's built in actions.
+    #[clap(long)]
+    actions: Option<PathBuf>,
+    /// JSON object of potencies and effects for the job's built in actions, keyed by name.
+    #[clap(long)]
+    overlay: Option<PathBuf>,
+    /// JSON object of base stats, eg. {"Strength": 5435}. Defaults to the job's example stats.
+    #[clap(long)]
+    stats: Option<PathBuf>,
+    /// Rotation file in priority order, or a JSON list for `.json` files. Defaults to the job's
+    /// example rotation.
+    #[clap(long)]
+    rotation: Option<PathBuf>,
+}
 
-    let now = sim_state.tick();
-    // TODO: Temporary. Sim 15s for now while in dev.
-    println!("============ Tick: {}", now);
-    if now >= 15000 {
-        std::process::exit(0);
+impl ActorArgs {
+    fn load(&self) -> Result<ActorConfig, ConfigError> {
+        load_actor(
+            self.job,
+            ActorFiles {
+                actions: self.actions.as_deref(),
+                overlay: self.overlay.as_deref(),
+                stats: self.stats.as_deref(),
+                rotation: self.rotation.as_deref(),
+            },
+        )
     }
 }
 
-fn reset_stats(mut query: Query<&mut Stats>) {
-    for mut stats in query.iter_mut() {
-        stats.reset();
+#[derive(Args)]
+struct SimArgs {
+    /// Fight duration in seconds.
+    #[clap(long, default_value_t = 15.0, parse(try_from_str = parse_duration))]
+    duration: f64,
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Milliseconds added to every action's animation lock, eg. for ping.
+    #[clap(long, default_value_t = 0)]
+    latency: SimTime,
+    /// Game version whose damage rules apply, eg. endwalker for crit and direct hit buffs raising
+    /// the damage of guaranteed crits and direct hits.
+    #[clap(long, default_value = "shadowbringers")]
+    ruleset: Ruleset,
+    /// `expected` weights crits and direct hits by their chance instead of rolling them, for a
+    /// single deterministic run.
+    #[clap(long, default_value = "random")]
+    damage: DamageMode,
+    #[clap(long, arg_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+impl SimArgs {
+    fn simulation(&self, actor: ActorConfig) -> Simulation {
+        let simulation = Simulation::new()
+            .with_actor(actor)
+            .with_duration((self.duration * 1000.0) as SimTime)
+            .with_latency(self.latency)
+            .with_ruleset(self.ruleset)
+            .with_damage_mode(self.damage);
+        match self.seed {
+            Some(seed) => simulation.with_seed(seed),
+            None => simulation,
+        }
     }
 }
 
-fn reset_active_combos(mut query: Query<&mut ActiveCombos>) {
-    for mut active_combos in query.iter_mut() {
-        active_combos.reset();
+// parse_duration only accepts a positive, finite number of seconds.
+fn parse_duration(source: &str) -> Result<f64, String> {
+    let duration: f64 = source.parse().map_err(|err| format!("{}", err))?;
+    if !duration.is_finite() || duration <= 0.0 {
+        return Err(format!(
+            "duration must be a positive number of seconds, got {}",
+            source
+        ));
     }
+    Ok(duration)
 }
 
-fn remove_expired_status_effects(
-    sim_state_query: Query<&SimState>,
-    mut status_effects_query: Query<&mut StatusEffects>,
-) {
-    let sim_state = sim_state_query
-        .single()
-        .expect("There should always be exactly one sim state.");
-    let sim_time = sim_state.now();
-    for mut status_effects in status_effects_query.iter_mut() {
-        status_effects.remove_expired(sim_time);
+// single_run_only rejects formats that only make sense for a single run.
+fn single_run_only(format: Format) -> Result<(), Box<dyn Error>> {
+    if format == Format::EventsCsv {
+        return Err("--format events-csv needs the event log of a single run; use run".into());
     }
+    Ok(())
 }
 
-#[derive(Debug)]
-struct StatusEffectApplyBundle {
-    status_effect: StatusEffect,
-    source: Entity,
-    target: Entity,
+#[derive(Args)]
+struct BatchArgs {
+    #[clap(long, default_value_t = 1000)]
+    runs: usize,
+    /// Defaults to one thread per core.
+    #[clap(long)]
+    threads: Option<usize>,
 }
-fn process_status_effects(sim_state_query: Query<&SimState>, mut actor_query: QueryActor) {
-    let sim = sim_state_query
-        .single()
-        .expect("There should always be exactly one sim state.");
 
-    let mut bundles = Vec::<StatusEffectApplyBundle>::default();
-    for (entity, _, _, _, _, _, status_effects, _, _) in actor_query.iter_mut() {
-        for effect in status_effects.iter() {
-            bundles.push(StatusEffectApplyBundle {
-                status_effect: effect.clone(),
-                source: effect.source,
-                target: entity,
-            });
+impl BatchArgs {
+    fn batch<F: Fn() -> Simulation + Sync>(&self, seed: Option<u64>, factory: F) -> Batch<F> {
+        let mut batch = Batch::new(self.runs, factory);
+        if let Some(seed) = seed {
+            batch = batch.with_seed(seed);
         }
-    }
-    for bundle in bundles {
-        bundle
-            .status_effect
-            .apply(sim, &mut actor_query, bundle.source, bundle.target);
+        if let Some(threads) = self.threads {
+            batch = batch.with_threads(threads);
+        }
+        batch
     }
 }
 
-struct ActionPerformBundle {
-    action: Action,
-    source_entity: Entity,
-    target_entity: Entity,
+#[derive(ArgEnum, Copy, Clone, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+    /// The per-action damage breakdown, or one row per run or candidate.
+    Csv,
+    /// The full event log of a single run.
+    EventsCsv,
 }
-fn perform_actions(
-    sim_state_query: Query<&SimState>,
-    mut actor_queries: QuerySet<(Query<ActorTuple, With<Target>>, QueryActor)>,
-) {
-    let sim = sim_state_query
-        .single()
-        .expect("There should always be exactly one sim state.");
-    let sim_time = sim.now();
 
-    let (temp_target_entity, _, _, _, _, _, _, _, _) = actor_queries
-        .q0_mut()
-        .single_mut()
-        .expect("There should always be exactly one sim state.");
-    let target_entity = temp_target_entity.clone();
+#[derive(ArgEnum, Copy, Clone, PartialEq, Eq)]
+enum ValidateFormat {
+    Text,
+    Json,
+}
 
-    let mut actor_query = actor_queries.q1_mut();
-    let mut perform_bundles = Vec::<ActionPerformBundle>::default();
-    for (entity, _, actions, rotation, recast_expirations, _, _, _, active_combos) in
-        actor_query.iter_mut()
-    {
-        match rotation.get_next_action_id(sim_time, &recast_expirations, &active_combos) {
-            Some(action_id) => match actions.get(&action_id) {
-                Some(action) => perform_bundles.push(ActionPerformBundle {
-                    action: action.clone(),
-                    source_entity: entity,
-                    target_entity: target_entity,
-                }),
-                None => (),
-            },
-            None => (),
-        };
+fn main() {
+    if let Err(err) = run(Cli::parse()) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
     }
+}
 
-    for bundle in perform_bundles {
-        println!(
-            ">>>> ACTION [{}s]: {}",
-            sim_time as f64 / 1000.0,
-            bundle.action.name
-        );
-        bundle.action.perform(
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let mut out = stdout();
+    match cli.command {
+        Command::Run { actor, sim } => {
+            let result = sim.simulation(actor.load()?).run();
+            match sim.format {
+                Format::Text => {
+                    for event in &result.events {
+                        writeln!(out, "{}", event)?;
+                    }
+                    writeln!(out, "{}", result)?;
+                }
+                Format::Json => export::write_json(&result, &mut out)?,
+                Format::Csv => export::write_breakdown_csv(&result, &mut out)?,
+                Format::EventsCsv => export::write_events_csv(&result, &mut out)?,
+            }
+        }
+        Command::Batch { actor, sim, batch } => {
+            single_run_only(sim.format)?;
+            let actor = actor.load()?;
+            let result = batch
+                .batch(sim.seed, || sim.simulation(actor.clone()))
+                .run();
+            match sim.format {
+                Format::Text => writeln!(out, "{}", result)?,
+                Format::Json => serde_json::to_writer_pretty(
+                    &mut out,
+                    &json!({
+                        "seed": result.seed,
+                        "runs": result.runs.len(),
+                        "dps": result.dps,
+                    }),
+                )?,
+                Format::Csv => {
+                    let mut writer = csv::Writer::from_writer(&mut out);
+                    writer.write_record(["run", "seed", "total_damage", "dps"])?;
+                    for (run, run_result) in result.runs.iter().enumerate() {
+                        writer.write_record(&[
+                            run.to_string(),
+                            run_result.seed.to_string(),
+                            run_result.total_damage.to_string(),
+                            run_result.dps.to_string(),
+                        ])?;
+                    }
+                    writer.flush()?;
+                }
+                Format::EventsCsv => unreachable!("Rejected by single_run_only."),
+            }
+        }
+        Command::Compare {
+            job,
+            actions,
+            overlay,
+            stats,
+            rotations,
             sim,
-            &mut actor_query,
-            bundle.source_entity,
-            bundle.target_entity,
-        );
+            batch,
+        } => {
+            single_run_only(sim.format)?;
+            let candidates = stats.len().max(rotations.len()).max(1);
+            for paths in [&stats, &rotations] {
+                if paths.len() > 1 && paths.len() != candidates {
+                    return Err(
+                        "--stats and --rotation must be given once or once per candidate".into(),
+                    );
+                }
+            }
+            let pick = |paths: &Vec<PathBuf>, candidate: usize| {
+                paths.get(candidate).or_else(|| paths.first()).cloned()
+            };
+            // Every candidate runs with the same seeds so differences come from the files alone.
+            let seed = sim.seed.unwrap_or_else(rand::random);
+            let mut rows = vec![];
+            for candidate in 0..candidates {
+                let stats = pick(&stats, candidate);
+                let rotation = pick(&rotations, candidate);
+                let actor = load_actor(
+                    job,
+                    ActorFiles {
+                        actions: actions.as_deref(),
+                        overlay: overlay.as_deref(),
+                        stats: stats.as_deref(),
+                        rotation: rotation.as_deref(),
+                    },
+                )?;
+                let result = batch
+                    .batch(Some(seed), || sim.simulation(actor.clone()))
+                    .run();
+                let label = [stats, rotation]
+                    .iter()
+                    .flatten()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<String>>()
+                    .join(" + ");
+                rows.push((label, result.dps.mean));
+            }
+            let baseline = rows[0].1;
+            match sim.format {
+                Format::Text => {
+                    writeln!(out, "Seed: {}", seed)?;
+                    for (label, mean) in &rows {
+                        writeln!(out, "{:.2} ({:+.2}) {}", mean, mean - baseline, label)?;
+                    }
+                }
+                Format::Json => serde_json::to_writer_pretty(
+                    &mut out,
+                    &json!({
+                        "seed": seed,
+                        "candidates": rows
+                            .iter()
+                            .map(|(label, mean)| json!({
+                                "label": label,
+                                "mean_dps": mean,
+                                "difference": mean - baseline,
+                            }))
+                            .collect::<Vec<_>>(),
+                    }),
+                )?,
+                Format::Csv => {
+                    let mut writer = csv::Writer::from_writer(&mut out);
+                    writer.write_record(["label", "mean_dps", "difference"])?;
+                    for (label, mean) in &rows {
+                        writer.write_record(&[
+                            label.clone(),
+                            mean.to_string(),
+                            (mean - baseline).to_string(),
+                        ])?;
+                    }
+                    writer.flush()?;
+                }
+                Format::EventsCsv => unreachable!("Rejected by single_run_only."),
+            }
+        }
+        Command::Validate {
+            actor,
+            format,
+            latency,
+        } => {
+            let violations = validate(&actor.load()?, latency);
+            match format {
+                ValidateFormat::Text => {
+                    for violation in &violations {
+                        writeln!(out, "{}", violation)?;
+                    }
+                }
+                ValidateFormat::Json => {
+                    serde_json::to_writer_pretty(&mut out, &violations)?;
+                    writeln!(out)?;
+                }
+            }
+            if !violations.is_empty() {
+                return Err(format!("{} rotation violations", violations.len()).into());
+            }
+            if format == ValidateFormat::Text {
+                writeln!(out, "ok")?;
+            }
+        }
     }
-}
-
-#[derive(Debug, Hash, Eq, PartialEq, Clone, SystemLabel)]
-enum SimLabel {
-    Tick,
-    Setup,
-    Calculate,
-    Execute,
-}
-
-fn main() {
-    App::build()
-        .insert_resource(ScheduleRunnerSettings::run_loop(Duration::new(0, 0)))
-        .add_plugin(ScheduleRunnerPlugin::default())
-        .add_startup_system(setup.system())
-        .add_system_set(
-            SystemSet::new()
-                .label(SimLabel::Tick)
-                .with_system(tick.system()),
-        )
-        .add_system_set(
-            SystemSet::new()
-                .label(SimLabel::Setup)
-                .with_system(reset_stats.system())
-                .with_system(reset_active_combos.system())
-                .with_system(remove_expired_status_effects.system())
-                .after(SimLabel::Tick),
-        )
-        .add_system_set(
-            SystemSet::new()
-                .label(SimLabel::Calculate)
-                .with_system(process_status_effects.system())
-                .after(SimLabel::Setup),
-        )
-        .add_system_set(
-            SystemSet::new()
-                .label(SimLabel::Execute)
-                .with_system(perform_actions.system())
-                .after(SimLabel::Calculate),
-        )
-        .run();
+    Ok(())
 }
@@ -0,0 +1,393 @@
+use crate::actor::action::{Action, Actions, CastTime, DEFAULT_ANIMATION_LOCK};
+use crate::actor::apply::{
+    Apply, ApplyCombo, DoDamageOverTime, DoDirectDamage, GiveStatusEffect, ModifyGauge, StartGcd,
+    StartRecast, DEFAULT_GCD,
+};
+use crate::actor::calc::AttackType;
+use crate::actor::stat::{SpecialStat, Stat};
+use crate::actor::status_effect::status::{
+    ModifyDamageDealt, ModifyDamageTaken, ModifySpecialStat, ModifyStat, SetCombo, Status,
+    StatusFlag, StatusFlags,
+};
+use crate::sim::SimTime;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// ActionDefinition is an Action as written in a data file. Results that refer to an action id
+/// default to the action being defined.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ActionDefinition {
+    pub id: u32,
+    pub name: String,
+    #[serde(default)]
+    pub ogcd: bool,
+    // cast is how long the action is cast for; actions without one are instant.
+    #[serde(default)]
+    pub cast: Option<CastDefinition>,
+    #[serde(default = "default_animation_lock")]
+    pub animation_lock: SimTime,
+    pub results: Vec<ResultDefinition>,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CastDefinition {
+    pub duration: SimTime,
+    // speed is the stat that shortens the cast, SkillSpeed unless the action is a spell.
+    #[serde(default = "default_speed")]
+    pub speed: Stat,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", deny_unknown_fields)]
+pub enum ResultDefinition {
+    DoDirectDamage {
+        potency: i64,
+        combo_action_id: Option<u32>,
+        combo_potency: Option<i64>,
+        #[serde(default)]
+        attack_type: AttackType,
+        #[serde(default)]
+        preserves_combo: bool,
+        // combo_results only apply when the combo is consumed.
+        #[serde(default)]
+        combo_results: Vec<ResultDefinition>,
+    },
+    DoDamageOverTime {
+        // name is the status the ticks show as on the target.
+        name: String,
+        potency: i64,
+        duration: SimTime,
+        #[serde(default)]
+        attack_type: AttackType,
+    },
+    StartRecast {
+        action_id: Option<u32>,
+        duration: SimTime,
+    },
+    GiveStatusEffect {
+        status: StatusDefinition,
+        #[serde(default)]
+        target_source: bool,
+    },
+    StartGcd {
+        #[serde(default = "default_gcd")]
+        duration: SimTime,
+        // speed is the stat that shortens the GCD, SkillSpeed unless the action is a spell.
+        #[serde(default = "default_speed")]
+        speed: Stat,
+    },
+    ApplyCombo {
+        action_id: Option<u32>,
+    },
+    ModifyGauge {
+        name: String,
+        amount: i64,
+        max: Option<i64>,
+    },
+}
+
+fn default_gcd() -> SimTime {
+    DEFAULT_GCD
+}
+
+fn default_animation_lock() -> SimTime {
+    DEFAULT_ANIMATION_LOCK
+}
+
+fn default_speed() -> Stat {
+    Stat::SkillSpeed
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatusDefinition {
+    pub name: String,
+    pub duration: SimTime,
+    #[serde(default)]
+    pub flags: Vec<StatusFlag>,
+    #[serde(default)]
+    pub effects: Vec<EffectDefinition>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", deny_unknown_fields)]
+pub enum EffectDefinition {
+    ModifyStat { stat: Stat, amount: i64 },
+    ModifySpecialStat { stat: SpecialStat, amount: i64 },
+    ModifyDamageDealt { percent: i64 },
+    ModifyDamageTaken { percent: i64 },
+    SetCombo { action_id: u32 },
+}
+
+impl ActionDefinition {
+    pub fn to_action(&self) -> Action {
+        Action {
+            id: self.id,
+            name: self.name.clone(),
+            ogcd: self.ogcd,
+            cast_time: self.cast.map(|cast| CastTime {
+                base: cast.duration,
+                speed: cast.speed,
+            }),
+            animation_lock: self.animation_lock,
+            results: self
+                .results
+                .iter()
+                .map(|result| result.to_apply(self.id))
+                .collect(),
+        }
+    }
+}
+
+impl ResultDefinition {
+    fn to_apply(&self, own_id: u32) -> Arc<dyn Apply + Send + Sync> {
+        match self {
+            ResultDefinition::DoDirectDamage {
+                potency,
+                combo_action_id,
+                combo_potency,
+                attack_type,
+                preserves_combo,
+                combo_results,
+            } => Arc::new(DoDirectDamage {
+                action_id: own_id,
+                potency: *potency,
+                combo_action_id: *combo_action_id,
+                combo_potency: *combo_potency,
+                attack_type: *attack_type,
+                preserves_combo: *preserves_combo,
+                combo_results: combo_results
+                    .iter()
+                    .map(|result| result.to_apply(own_id))
+                    .collect(),
+            }),
+            ResultDefinition::DoDamageOverTime {
+                name,
+                potency,
+                duration,
+                attack_type,
+            } => Arc::new(DoDamageOverTime {
+                action_id: own_id,
+                name: name.clone(),
+                potency: *potency,
+                duration: *duration,
+                attack_type: *attack_type,
+            }),
+            ResultDefinition::StartRecast {
+                action_id,
+                duration,
+            } => Arc::new(StartRecast {
+                action_id: action_id.unwrap_or(own_id),
+                duration: *duration,
+            }),
+            ResultDefinition::GiveStatusEffect {
+                status,
+                target_source,
+            } => Arc::new(GiveStatusEffect {
+                status: status.to_status(),
+                target_source: *target_source,
+            }),
+            ResultDefinition::StartGcd { duration, speed } => {
+                Arc::new(StartGcd::new(*duration).with_speed(*speed))
+            }
+            ResultDefinition::ApplyCombo { action_id } => {
+                Arc::new(ApplyCombo(action_id.unwrap_or(own_id)))
+            }
+            ResultDefinition::ModifyGauge { name, amount, max } => Arc::new(ModifyGauge {
+                name: name.clone(),
+                amount: *amount,
+                max: *max,
+            }),
+        }
+    }
+}
+
+impl StatusDefinition {
+    pub fn to_status(&self) -> Status {
+        Status {
+            name: self.name.clone(),
+            duration: self.duration,
+            flags: StatusFlags::new(&self.flags),
+            effects: self
+                .effects
+                .iter()
+                .map(EffectDefinition::to_apply)
+                .collect(),
+        }
+    }
+}
+
+impl EffectDefinition {
+    fn to_apply(&self) -> Arc<dyn Apply + Send + Sync> {
+        match self {
+            EffectDefinition::ModifyStat { stat, amount } => Arc::new(ModifyStat {
+                stat: *stat,
+                amount: *amount,
+            }),
+            EffectDefinition::ModifySpecialStat { stat, amount } => Arc::new(ModifySpecialStat {
+                stat: *stat,
+                amount: *amount,
+            }),
+            EffectDefinition::ModifyDamageDealt { percent } => {
+                Arc::new(ModifyDamageDealt { percent: *percent })
+            }
+            EffectDefinition::ModifyDamageTaken { percent } => {
+                Arc::new(ModifyDamageTaken { percent: *percent })
+            }
+            EffectDefinition::SetCombo { action_id } => Arc::new(SetCombo(*action_id)),
+        }
+    }
+}
+
+// parse_actions reads a JSON list of ActionDefinitions into Actions.
+pub fn parse_actions(source: &str) -> Result<Actions, serde_json::Error> {
+    let definitions: Vec<ActionDefinition> = serde_json::from_str(source)?;
+    let mut actions = Actions::default();
+    for definition in &definitions {
+        actions.add(definition.to_action());
+    }
+    Ok(actions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn action() {
+        let definition: ActionDefinition = serde_json::from_str(
+            r#"{
+                "id": 2,
+                "name": "Vorpal Thrust",
+                "results": [
+                    {"type": "DoDirectDamage", "potency": 140, "combo_action_id": 1, "combo_potency": 350},
+                    {"type": "StartGcd"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let action = definition.to_action();
+        assert_eq!(2, action.id);
+        assert_eq!("Vorpal Thrust", action.name);
+        assert!(!action.ogcd);
+        assert_eq!(2, action.results.len());
+        assert!(matches!(
+            definition.results[1],
+            ResultDefinition::StartGcd {
+                duration: DEFAULT_GCD,
+                speed: Stat::SkillSpeed
+            }
+        ));
+    }
+
+    #[test]
+    fn action_cast() {
+        let definition: ActionDefinition = serde_json::from_str(
+            r#"{"id": 1, "name": "Holy Spirit", "cast": {"duration": 1500, "speed": "SpellSpeed"}, "results": []}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Some(CastTime {
+                base: 1500,
+                speed: Stat::SpellSpeed
+            }),
+            definition.to_action().cast_time
+        );
+    }
+
+    #[test]
+    fn damage_over_time() {
+        let definition: ActionDefinition = serde_json::from_str(
+            r#"{
+                "id": 88,
+                "name": "Chaos Thrust",
+                "results": [{"type": "DoDamageOverTime", "name": "Chaos Thrust", "potency": 50, "duration": 24000}]
+            }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            &definition.results[0],
+            ResultDefinition::DoDamageOverTime {
+                potency: 50,
+                duration: 24000,
+                attack_type: AttackType::PHYSICAL,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn modify_gauge() {
+        let definition: ActionDefinition = serde_json::from_str(
+            r#"{
+                "id": 16145,
+                "name": "Solid Barrel",
+                "results": [{"type": "ModifyGauge", "name": "Cartridges", "amount": 1, "max": 2}]
+            }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            &definition.results[0],
+            ResultDefinition::ModifyGauge {
+                name,
+                amount: 1,
+                max: Some(2),
+            } if name == "Cartridges"
+        ));
+    }
+
+    #[test]
+    fn status() {
+        let definition: StatusDefinition = serde_json::from_str(
+            r#"{
+                "name": "Life Surge",
+                "duration": 10000,
+                "flags": ["ExpireOnDirectDamage"],
+                "effects": [{"type": "ModifySpecialStat", "stat": "CriticalHitPercentOverride", "amount": 100}]
+            }"#,
+        )
+        .unwrap();
+        let status = definition.to_status();
+        assert_eq!("Life Surge", status.name);
+        assert_eq!(10000, status.duration);
+        assert!(status.has_flag(&StatusFlag::ExpireOnDirectDamage));
+        assert_eq!(1, status.effects.len());
+    }
+
+    #[test]
+    fn status_damage_multiplier() {
+        let definition: StatusDefinition = serde_json::from_str(
+            r#"{
+                "name": "Lance Charge",
+                "duration": 20000,
+                "effects": [{"type": "ModifyDamageDealt", "percent": 15}]
+            }"#,
+        )
+        .unwrap();
+        let status = definition.to_status();
+        assert_eq!(Some(1.15), status.effects[0].damage_dealt());
+    }
+
+    #[test]
+    fn parse_actions_rejects_unknown_results() {
+        assert!(
+            parse_actions(r#"[{"id": 1, "name": "Jump", "results": [{"type": "Jump"}]}]"#).is_err()
+        );
+        assert!(parse_actions(
+            r#"[{"id": 1, "name": "Jump", "results": [{"type": "StartGcd", "duraton": 1}]}]"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn parse_actions_by_id() {
+        let actions = parse_actions(
+            r#"[{"id": 1, "name": "True Thrust", "results": []}, {"id": 3, "name": "Jump", "ogcd": true, "results": []}]"#,
+        )
+        .unwrap();
+        assert_eq!("True Thrust", actions.get(&1).unwrap().name);
+        assert!(actions.get(&3).unwrap().ogcd);
+    }
+}
@@ -1,14 +1,23 @@
-use rand::{random, Rng};
-use std::sync::Arc;
+use crate::encounter::{Encounter, EndReason};
+use rand::rngs::StdRng;
+use rand::{random, Rng, SeedableRng};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Mutex};
 
 pub type SimTime = u64;
 
-pub const TICKS_PER_SECOND: SimTime = 1;
-pub const MS_PER_TICK: SimTime = 1000 / TICKS_PER_SECOND;
+// SERVER_TICK is how often damage over time ticks, on a clock shared by every actor.
+pub const SERVER_TICK: SimTime = 3000;
 
 pub trait SimRng {
     fn random(&self) -> f64;
     fn random_from_range(&self, low_inclusive: i64, high_exclusive: i64) -> i64;
+
+    // seed returns the seed needed to reproduce this rng's sequence, if it has one.
+    fn seed(&self) -> Option<u64> {
+        None
+    }
 }
 
 struct RealRng {}
@@ -23,36 +32,418 @@ impl SimRng for RealRng {
     }
 }
 
+/// SeededRng produces the same sequence of rolls for the same seed, so a run can be reproduced.
+pub struct SeededRng {
+    seed: u64,
+    rng: Mutex<StdRng>,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng {
+            seed,
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    // from_random_seed picks a seed at random. The seed is still recorded so the run can be replayed.
+    pub fn from_random_seed() -> Self {
+        SeededRng::new(random::<u64>())
+    }
+}
+
+impl SimRng for SeededRng {
+    fn random(&self) -> f64 {
+        self.rng.lock().unwrap().gen::<f64>()
+    }
+
+    fn random_from_range(&self, low_inclusive: i64, high_exclusive: i64) -> i64 {
+        self.rng
+            .lock()
+            .unwrap()
+            .gen_range(low_inclusive..high_exclusive)
+    }
+
+    fn seed(&self) -> Option<u64> {
+        Some(self.seed)
+    }
+}
+
+/// DamageMode selects how damage rolls are resolved.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum DamageMode {
+    // Random rolls crit, direct hit and damage variance with the sim rng.
+    #[default]
+    Random,
+    // Expected weights crit and direct hit by their probability and uses the mean damage variance,
+    // so a single run gives the expected damage of a rotation.
+    Expected,
+}
+
+// Damage modes parse from their name, ignoring case, eg. "expected".
+impl std::str::FromStr for DamageMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "random" => Ok(DamageMode::Random),
+            "expected" => Ok(DamageMode::Expected),
+            _ => Err(format!("Unknown damage mode: {}", s)),
+        }
+    }
+}
+
+/// Ruleset selects which game version's damage rules apply where they have changed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Ruleset {
+    #[default]
+    Shadowbringers,
+    // Endwalker turns crit and direct hit rate bonuses on guaranteed crits and direct hits into
+    // extra damage.
+    Endwalker,
+}
+
+impl Ruleset {
+    pub fn converts_guaranteed_hit_bonuses(&self) -> bool {
+        matches!(self, Ruleset::Endwalker)
+    }
+}
+
+// Rulesets parse from their expansion name, ignoring case, eg. "endwalker".
+impl std::str::FromStr for Ruleset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "shadowbringers" => Ok(Ruleset::Shadowbringers),
+            "endwalker" => Ok(Ruleset::Endwalker),
+            _ => Err(format!("Unknown ruleset: {}", s)),
+        }
+    }
+}
+
 pub struct SimState {
     milliseconds: SimTime,
+    damage_mode: DamageMode,
+    ruleset: Ruleset,
+    // latency is added to every animation lock, eg. for the player's ping.
+    latency: SimTime,
+    // server_tick_offset is when the first server tick lands, since the server's clock doesn't
+    // start with the fight.
+    server_tick_offset: SimTime,
+    // last_server_tick is the last server tick an update ran at, so each tick is only seen once.
+    last_server_tick: Option<SimTime>,
+    on_server_tick: bool,
+    encounter: Encounter,
+    // end_reason is set once the encounter is over.
+    end_reason: Option<EndReason>,
+    // events holds the upcoming timestamps the simulation needs to stop at, earliest first.
+    events: BinaryHeap<Reverse<SimTime>>,
     pub rng: Arc<dyn SimRng + Sync + Send>,
 }
 
 impl SimState {
     pub fn new<T: SimRng + Sync + Send + 'static>(rng: T) -> Self {
+        SimState::from_arc(Arc::<T>::new(rng))
+    }
+
+    pub fn from_arc(rng: Arc<dyn SimRng + Sync + Send>) -> Self {
+        let mut events = BinaryHeap::new();
+        events.push(Reverse(0));
         SimState {
             milliseconds: 0,
-            rng: Arc::<T>::new(rng),
+            damage_mode: DamageMode::default(),
+            ruleset: Ruleset::default(),
+            latency: 0,
+            server_tick_offset: 0,
+            last_server_tick: None,
+            on_server_tick: false,
+            encounter: Encounter::default(),
+            end_reason: None,
+            events,
+            rng,
         }
     }
+
+    pub fn with_encounter(mut self, encounter: Encounter) -> Self {
+        self.encounter = encounter;
+        self
+    }
+
+    pub fn with_duration(self, duration: SimTime) -> Self {
+        self.with_encounter(Encounter::Duration(duration))
+    }
+
+    pub fn with_damage_mode(mut self, damage_mode: DamageMode) -> Self {
+        self.damage_mode = damage_mode;
+        self
+    }
+
+    pub fn with_ruleset(mut self, ruleset: Ruleset) -> Self {
+        self.ruleset = ruleset;
+        self
+    }
+
+    pub fn with_latency(mut self, latency: SimTime) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn with_server_tick_offset(mut self, offset: SimTime) -> Self {
+        self.server_tick_offset = offset % SERVER_TICK;
+        self
+    }
 }
 
 impl Default for SimState {
     fn default() -> Self {
-        SimState {
-            milliseconds: 0,
-            rng: Arc::<RealRng>::new(RealRng {}),
-        }
+        SimState::new(RealRng {})
     }
 }
 
 impl SimState {
+    /// Advances to the next scheduled timestamp, or to the end of the simulation when nothing else
+    /// is scheduled. Duplicate entries for the same timestamp are collapsed into one update.
     pub fn tick(&mut self) -> SimTime {
-        self.milliseconds += MS_PER_TICK;
+        let time_limit = self.encounter.time_limit();
+        let next = match self.events.pop() {
+            Some(Reverse(sim_time)) => sim_time,
+            None => time_limit,
+        };
+        while self.events.peek() == Some(&Reverse(next)) {
+            self.events.pop();
+        }
+        self.milliseconds = next.min(time_limit);
+        if self.milliseconds >= time_limit {
+            self.finish(self.encounter.time_limit_reason());
+        }
+        let at_server_tick = self
+            .milliseconds
+            .checked_sub(self.server_tick_offset)
+            .is_some_and(|elapsed| elapsed % SERVER_TICK == 0);
+        self.on_server_tick = at_server_tick && self.last_server_tick != Some(self.milliseconds);
+        if at_server_tick {
+            self.last_server_tick = Some(self.milliseconds);
+        }
         self.milliseconds
     }
 
+    /// Returns the first server tick after the current timestamp.
+    pub fn next_server_tick(&self) -> SimTime {
+        match self.milliseconds.checked_sub(self.server_tick_offset) {
+            Some(elapsed) => self.milliseconds + SERVER_TICK - elapsed % SERVER_TICK,
+            None => self.server_tick_offset,
+        }
+    }
+
+    /// Returns true for the first update at a server tick. Later updates at the same timestamp
+    /// return false so nothing ticks twice.
+    pub fn is_server_tick(&self) -> bool {
+        self.on_server_tick
+    }
+
+    /// Requests an update at `sim_time`. Timestamps in the past are ignored. Scheduling the current
+    /// timestamp runs another update without advancing time.
+    pub fn schedule(&mut self, sim_time: SimTime) {
+        if sim_time >= self.milliseconds {
+            self.events.push(Reverse(sim_time));
+        }
+    }
+
     pub fn now(&self) -> SimTime {
         self.milliseconds
     }
+
+    pub fn damage_mode(&self) -> DamageMode {
+        self.damage_mode
+    }
+
+    pub fn ruleset(&self) -> Ruleset {
+        self.ruleset
+    }
+
+    pub fn latency(&self) -> SimTime {
+        self.latency
+    }
+
+    pub fn encounter(&self) -> &Encounter {
+        &self.encounter
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.end_reason.is_some()
+    }
+
+    pub fn end_reason(&self) -> Option<EndReason> {
+        self.end_reason
+    }
+
+    pub fn finish(&mut self, reason: EndReason) {
+        if self.end_reason.is_none() {
+            self.end_reason = Some(reason);
+        }
+    }
+
+    /// Ends the encounter if `damage` has depleted the target's HP. A target that dies before the
+    /// encounter allows it is kept alive until then.
+    pub fn check_target_damage(&mut self, damage: i64) {
+        if !self.encounter.is_target_dead(damage) {
+            return;
+        }
+        let earliest_kill = self.encounter.earliest_kill();
+        if self.milliseconds >= earliest_kill {
+            self.finish(EndReason::TargetKilled);
+        } else {
+            self.schedule(earliest_kill);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damage_mode_from_str() {
+        assert_eq!(Ok(DamageMode::Random), "random".parse());
+        assert_eq!(Ok(DamageMode::Expected), "Expected".parse());
+        assert!("average".parse::<DamageMode>().is_err());
+    }
+
+    #[test]
+    fn seeded_rng() {
+        let first = SeededRng::new(42);
+        let second = SeededRng::new(42);
+        for _ in 0..10 {
+            assert_eq!(first.random(), second.random());
+            assert_eq!(
+                first.random_from_range(95, 106),
+                second.random_from_range(95, 106)
+            );
+        }
+        assert_eq!(Some(42), first.seed());
+    }
+
+    #[test]
+    fn seeded_rng_different_seeds() {
+        let first = SeededRng::new(1);
+        let second = SeededRng::new(2);
+        let first_rolls: Vec<f64> = (0..10).map(|_| first.random()).collect();
+        let second_rolls: Vec<f64> = (0..10).map(|_| second.random()).collect();
+        assert_ne!(first_rolls, second_rolls);
+    }
+
+    #[test]
+    fn tick() {
+        let mut sim = SimState::default();
+        assert_eq!(0, sim.tick());
+        sim.schedule(2500);
+        sim.schedule(700);
+        assert_eq!(700, sim.tick());
+        assert_eq!(2500, sim.tick());
+        assert_eq!(2500, sim.now());
+    }
+
+    #[test]
+    fn tick_collapses_duplicates() {
+        let mut sim = SimState::default();
+        sim.tick();
+        sim.schedule(1000);
+        sim.schedule(1000);
+        sim.schedule(2000);
+        assert_eq!(1000, sim.tick());
+        assert_eq!(2000, sim.tick());
+    }
+
+    #[test]
+    fn tick_same_timestamp() {
+        let mut sim = SimState::default();
+        sim.tick();
+        sim.schedule(0);
+        assert_eq!(0, sim.tick());
+        assert!(!sim.is_finished());
+    }
+
+    #[test]
+    fn server_tick() {
+        let mut sim = SimState::default().with_server_tick_offset(4000);
+        assert_eq!(0, sim.tick());
+        assert!(!sim.is_server_tick());
+        assert_eq!(1000, sim.next_server_tick());
+        sim.schedule(1000);
+        assert_eq!(1000, sim.tick());
+        assert!(sim.is_server_tick());
+        assert_eq!(4000, sim.next_server_tick());
+        // Later updates at the same timestamp don't tick again.
+        sim.schedule(1000);
+        sim.tick();
+        assert!(!sim.is_server_tick());
+        sim.schedule(2500);
+        sim.tick();
+        assert!(!sim.is_server_tick());
+        assert_eq!(4000, sim.next_server_tick());
+    }
+
+    #[test]
+    fn schedule_ignores_past() {
+        let mut sim = SimState::default().with_duration(5000);
+        sim.tick();
+        sim.schedule(1000);
+        sim.tick();
+        sim.schedule(500);
+        assert_eq!(5000, sim.tick());
+    }
+
+    #[test]
+    fn is_finished() {
+        let mut sim = SimState::default().with_duration(2000);
+        sim.tick();
+        sim.schedule(1000);
+        sim.schedule(3000);
+        assert!(!sim.is_finished());
+        sim.tick();
+        assert!(!sim.is_finished());
+        assert_eq!(2000, sim.tick());
+        assert!(sim.is_finished());
+        assert_eq!(Some(EndReason::DurationElapsed), sim.end_reason());
+    }
+
+    #[test]
+    fn check_target_damage() {
+        let mut sim = SimState::default().with_encounter(Encounter::TargetHp {
+            hp: 100,
+            time_limit: 2000,
+        });
+        sim.tick();
+        sim.check_target_damage(99);
+        assert!(!sim.is_finished());
+        sim.check_target_damage(100);
+        assert_eq!(Some(EndReason::TargetKilled), sim.end_reason());
+    }
+
+    #[test]
+    fn check_target_damage_before_earliest_kill() {
+        let mut sim = SimState::default().with_encounter(Encounter::KillTime {
+            hp: 100,
+            earliest: 1000,
+            latest: 2000,
+        });
+        sim.tick();
+        sim.check_target_damage(100);
+        assert!(!sim.is_finished());
+        assert_eq!(1000, sim.tick());
+        sim.check_target_damage(100);
+        assert_eq!(Some(EndReason::TargetKilled), sim.end_reason());
+    }
+
+    #[test]
+    fn enrage() {
+        let mut sim = SimState::default().with_encounter(Encounter::TargetHp {
+            hp: 100,
+            time_limit: 2000,
+        });
+        sim.tick();
+        assert_eq!(2000, sim.tick());
+        assert_eq!(Some(EndReason::Enrage), sim.end_reason());
+    }
 }
@@ -0,0 +1,1123 @@
+use crate::actor::action::Actions;
+use crate::actor::active_combos::ActiveCombos;
+use crate::actor::animation_lock::AnimationLock;
+use crate::actor::auto_attack::AutoAttack;
+use crate::actor::calc::lookup::Job;
+use crate::actor::casting::Casting;
+use crate::actor::damage::Damage;
+use crate::actor::job_gauge::JobGauge;
+use crate::actor::recast_expirations::RecastExpirations;
+use crate::actor::rotation::script::ScriptProgress;
+use crate::actor::rotation::Rotation;
+use crate::actor::stat::Stats;
+use crate::actor::status_effect::StatusEffects;
+use crate::actor::Target;
+use crate::combat_log::{CombatEvent, CombatLog};
+use crate::encounter::{Encounter, EndReason};
+use crate::sim::{DamageMode, Ruleset, SeededRng, SimRng, SimState, SimTime, SERVER_TICK};
+use crate::systems;
+use bevy_app::AppBuilder;
+use serde::Serialize;
+use std::sync::Arc;
+
+// ActorConfig holds everything needed to spawn a simulated actor.
+#[derive(Clone)]
+pub struct ActorConfig {
+    pub job: Job,
+    pub actions: Actions,
+    pub rotation: Rotation,
+    pub stats: Stats,
+}
+
+/// Headless simulation runner. Configure it with the builder methods, then call `run` to execute
+/// the schedule until the encounter is over.
+#[derive(Default)]
+pub struct Simulation {
+    actors: Vec<ActorConfig>,
+    encounter: Encounter,
+    damage_mode: DamageMode,
+    ruleset: Ruleset,
+    latency: SimTime,
+    rng: Option<Arc<dyn SimRng + Sync + Send>>,
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        Simulation::default()
+    }
+
+    pub fn with_actor(mut self, actor: ActorConfig) -> Self {
+        self.actors.push(actor);
+        self
+    }
+
+    pub fn with_encounter(mut self, encounter: Encounter) -> Self {
+        self.encounter = encounter;
+        self
+    }
+
+    pub fn with_duration(self, duration: SimTime) -> Self {
+        self.with_encounter(Encounter::Duration(duration))
+    }
+
+    pub fn with_damage_mode(mut self, damage_mode: DamageMode) -> Self {
+        self.damage_mode = damage_mode;
+        self
+    }
+
+    pub fn with_ruleset(mut self, ruleset: Ruleset) -> Self {
+        self.ruleset = ruleset;
+        self
+    }
+
+    // with_latency adds latency to every action's animation lock.
+    pub fn with_latency(mut self, latency: SimTime) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn with_rng<T: SimRng + Sync + Send + 'static>(mut self, rng: T) -> Self {
+        self.rng = Some(Arc::new(rng));
+        self
+    }
+
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.with_rng(SeededRng::new(seed))
+    }
+
+    pub fn run(self) -> SimulationResult {
+        let mut builder = AppBuilder::empty();
+        systems::add_systems(&mut builder);
+        let mut app = builder.app;
+
+        // Runs without an explicit rng still get a seed so they can be replayed.
+        let rng = self
+            .rng
+            .unwrap_or_else(|| Arc::new(SeededRng::from_random_seed()));
+        let seed = rng.seed();
+        // Damage over time ticks on the server's clock, which is out of step with the fight. Expected
+        // damage doesn't depend on the seed, so it ticks halfway between two fight ticks instead.
+        let server_tick_offset = match self.damage_mode {
+            DamageMode::Random => rng.random_from_range(0, SERVER_TICK as i64) as SimTime,
+            DamageMode::Expected => SERVER_TICK / 2,
+        };
+        let sim_state = SimState::from_arc(rng)
+            .with_encounter(self.encounter)
+            .with_damage_mode(self.damage_mode)
+            .with_ruleset(self.ruleset)
+            .with_latency(self.latency)
+            .with_server_tick_offset(server_tick_offset);
+        let sim_entity = app.world.spawn().insert(sim_state).id();
+        app.world.insert_resource(CombatLog::default());
+
+        for actor in self.actors {
+            app.world.spawn().insert_bundle((
+                actor.job,
+                actor.actions,
+                actor.rotation,
+                RecastExpirations::default(),
+                Damage::default(),
+                StatusEffects::default(),
+                actor.stats,
+                ActiveCombos::default(),
+                JobGauge::default(),
+                ScriptProgress::default(),
+                Casting::default(),
+                AnimationLock::default(),
+                AutoAttack::default(),
+            ));
+        }
+        let target_entity = app
+            .world
+            .spawn()
+            .insert_bundle((
+                Target::default(),
+                Job::None,
+                Actions::default(),
+                Rotation::default(),
+                RecastExpirations::default(),
+                Damage::default(),
+                StatusEffects::default(),
+                Stats::default(),
+                ActiveCombos::default(),
+                JobGauge::default(),
+                ScriptProgress::default(),
+                Casting::default(),
+                AnimationLock::default(),
+                AutoAttack::default(),
+            ))
+            .id();
+
+        loop {
+            app.update();
+            let sim_state = app
+                .world
+                .get::<SimState>(sim_entity)
+                .expect("There should always be exactly one sim state.");
+            if sim_state.is_finished() {
+                break;
+            }
+        }
+
+        let sim_state = app
+            .world
+            .get::<SimState>(sim_entity)
+            .expect("There should always be exactly one sim state.");
+        let damage = app
+            .world
+            .get::<Damage>(target_entity)
+            .expect("The target should always have a Damage component.");
+        let duration = sim_state.now();
+        let end_reason = sim_state
+            .end_reason()
+            .expect("A finished simulation should have an end reason.");
+        let total_damage = damage.total();
+        let damage = damage.clone();
+        let log = app
+            .world
+            .remove_resource::<CombatLog>()
+            .expect("The combat log should always be present.");
+        SimulationResult {
+            duration,
+            end_reason,
+            seed,
+            total_damage,
+            damage,
+            events: log.into_events(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationResult {
+    pub duration: SimTime,
+    pub end_reason: EndReason,
+    // seed is the rng seed the run used, if the rng was seeded.
+    pub seed: Option<u64>,
+    pub total_damage: i64,
+    // damage is the target's damage breakdown by action, source and hit type.
+    #[serde(skip)]
+    pub damage: Damage,
+    // events is the combat log of the run, in the order things happened.
+    pub events: Vec<CombatEvent>,
+}
+
+impl SimulationResult {
+    pub fn dps(&self) -> f64 {
+        if self.duration == 0 {
+            return 0.0;
+        }
+        self.total_damage as f64 / (self.duration as f64 / 1000.0)
+    }
+}
+
+impl std::fmt::Display for SimulationResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Encounter ended at {}s ({})",
+            self.duration as f64 / 1000.0,
+            self.end_reason
+        )?;
+        if let Some(seed) = self.seed {
+            writeln!(f, "Seed: {}", seed)?;
+        }
+        writeln!(f, "Total damage: {}", self.total_damage)?;
+        write!(f, "DPS: {:.2}", self.dps())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::action::{Action, CastTime};
+    use crate::actor::apply::Apply;
+    use crate::actor::apply::{
+        ApplyCombo, DoDamageOverTime, DoDirectDamage, GiveStatusEffect, ModifyGauge, StartGcd,
+        StartRecast,
+    };
+    use crate::actor::auto_attack::AUTO_ATTACK_ID;
+    use crate::actor::rotation::script::{Script, ScriptMode, ScriptStep};
+    use crate::actor::rotation::{
+        CheckGauge, CheckNot, CheckTargetHasDebuff, Comparison, RotationEntry,
+    };
+    use crate::actor::stat::{SpecialStat, Stat};
+    use crate::actor::status_effect::status::{
+        ModifyDamageDealt, ModifyDamageTaken, ModifySpecialStat, ModifyStat, Status, StatusFlag,
+        StatusFlags,
+    };
+    use crate::combat_log::CombatEventKind;
+
+    struct FakeRng {}
+
+    impl SimRng for FakeRng {
+        fn random(&self) -> f64 {
+            1.0
+        }
+        fn random_from_range(&self, _low_inclusive: i64, _high_exclusive: i64) -> i64 {
+            100
+        }
+    }
+
+    fn actor() -> ActorConfig {
+        let mut actions = Actions::default();
+        let mut rotation = Rotation::default();
+        let true_thrust = Action {
+            id: 1,
+            name: "True Thrust".into(),
+            results: vec![
+                Arc::new(DoDirectDamage {
+                    action_id: 1,
+                    potency: 290,
+                    ..Default::default()
+                }),
+                Arc::new(StartGcd::default()),
+            ],
+            ..Default::default()
+        };
+        rotation.add(RotationEntry::new(&true_thrust));
+        actions.add(true_thrust);
+
+        let mut stats = Stats::default();
+        stats.set_base(Stat::PhysicalWeaponDamage, 134);
+        stats.set_base(Stat::AttackPower, 5435);
+        stats.set_base(Stat::Determination, 2965);
+        stats.set_base(Stat::Tenacity, 606);
+        ActorConfig {
+            job: Job::DRG,
+            actions,
+            rotation,
+            stats,
+        }
+    }
+
+    #[test]
+    fn run() {
+        let result = Simulation::new()
+            .with_actor(actor())
+            .with_duration(10000)
+            .with_rng(FakeRng {})
+            .run();
+        assert_eq!(10000, result.duration);
+        assert!(result.total_damage > 0);
+    }
+
+    #[test]
+    fn run_is_repeatable() {
+        let first = Simulation::new()
+            .with_actor(actor())
+            .with_rng(FakeRng {})
+            .run();
+        let second = Simulation::new()
+            .with_actor(actor())
+            .with_rng(FakeRng {})
+            .run();
+        assert_eq!(first.total_damage, second.total_damage);
+    }
+
+    #[test]
+    fn run_records_events() {
+        let result = Simulation::new()
+            .with_actor(actor())
+            .with_duration(2501)
+            .with_rng(FakeRng {})
+            .run();
+        let kinds: Vec<&CombatEventKind> = result.events.iter().map(|event| &event.kind).collect();
+        let damage = match kinds[2] {
+            CombatEventKind::DamageDealt { amount, .. } => *amount,
+            _ => panic!("Expected damage after the first action."),
+        };
+        assert_eq!(
+            vec![
+                &CombatEventKind::ActionUsed {
+                    action_id: 1,
+                    name: "True Thrust".into()
+                },
+                &CombatEventKind::AnimationLockStarted { duration: 600 },
+                &CombatEventKind::DamageDealt {
+                    action_id: 1,
+                    potency: 290,
+                    amount: damage,
+                    critical_hit: false,
+                    direct_hit: false,
+                    multipliers: vec![],
+                    buffs: vec![],
+                },
+                &CombatEventKind::GcdStarted { duration: 2500 },
+            ],
+            kinds[..4].to_vec()
+        );
+        assert_eq!(8, result.events.len());
+        assert_eq!(2500, result.events[4].time);
+        assert_eq!(2 * damage, result.total_damage);
+        assert_eq!(2, result.damage.by_action(1).unwrap().hits);
+    }
+
+    #[test]
+    fn run_with_seed_is_reproducible() {
+        let first = Simulation::new()
+            .with_actor(actor())
+            .with_duration(60000)
+            .with_seed(1234)
+            .run();
+        let second = Simulation::new()
+            .with_actor(actor())
+            .with_duration(60000)
+            .with_seed(1234)
+            .run();
+        assert_eq!(Some(1234), first.seed);
+        assert_eq!(first.total_damage, second.total_damage);
+    }
+
+    #[test]
+    fn run_records_random_seed() {
+        let first = Simulation::new().with_actor(actor()).run();
+        let replay = Simulation::new()
+            .with_actor(actor())
+            .with_seed(first.seed.unwrap())
+            .run();
+        assert_eq!(first.total_damage, replay.total_damage);
+    }
+
+    #[test]
+    fn run_expected_damage_ignores_seed() {
+        let first = Simulation::new()
+            .with_actor(actor())
+            .with_damage_mode(DamageMode::Expected)
+            .with_seed(1)
+            .run();
+        let second = Simulation::new()
+            .with_actor(actor())
+            .with_damage_mode(DamageMode::Expected)
+            .with_seed(2)
+            .run();
+        assert_eq!(first.total_damage, second.total_damage);
+
+        // The server tick offset, and so the number of damage over time ticks, is fixed too.
+        let mut actor = actor();
+        let chaos_thrust = Action {
+            id: 2,
+            name: "Chaos Thrust".into(),
+            results: vec![
+                Arc::new(DoDamageOverTime {
+                    action_id: 2,
+                    name: "Chaos Thrust".into(),
+                    potency: 50,
+                    duration: 24000,
+                    attack_type: Default::default(),
+                }),
+                Arc::new(StartGcd::default()),
+            ],
+            ..Default::default()
+        };
+        actor.rotation = Rotation::default();
+        actor.rotation.add(RotationEntry::new(&chaos_thrust));
+        actor.actions.add(chaos_thrust);
+        let total_damage = |seed| {
+            Simulation::new()
+                .with_actor(actor.clone())
+                .with_duration(4000)
+                .with_damage_mode(DamageMode::Expected)
+                .with_seed(seed)
+                .run()
+                .total_damage
+        };
+        for seed in 1..5 {
+            assert_eq!(total_damage(0), total_damage(seed));
+        }
+    }
+
+    #[test]
+    fn run_advances_to_gcd_expiration() {
+        // With a 2.5s GCD, 10s fits hits at 0, 2.5, 5 and 7.5s; the fifth lands at exactly 10s.
+        let four_hits = Simulation::new()
+            .with_actor(actor())
+            .with_duration(10000)
+            .with_rng(FakeRng {})
+            .run();
+        let five_hits = Simulation::new()
+            .with_actor(actor())
+            .with_duration(10001)
+            .with_rng(FakeRng {})
+            .run();
+        assert_eq!(
+            four_hits.total_damage / 4,
+            five_hits.total_damage - four_hits.total_damage
+        );
+    }
+
+    #[test]
+    fn run_until_target_killed() {
+        let single_hit = Simulation::new()
+            .with_actor(actor())
+            .with_duration(1)
+            .with_rng(FakeRng {})
+            .run();
+        let result = Simulation::new()
+            .with_actor(actor())
+            .with_encounter(Encounter::TargetHp {
+                hp: single_hit.total_damage * 3,
+                time_limit: 60000,
+            })
+            .with_rng(FakeRng {})
+            .run();
+        assert_eq!(EndReason::TargetKilled, result.end_reason);
+        assert_eq!(5000, result.duration);
+        assert_eq!(single_hit.total_damage * 3, result.total_damage);
+    }
+
+    #[test]
+    fn run_until_enrage() {
+        let result = Simulation::new()
+            .with_actor(actor())
+            .with_encounter(Encounter::KillTime {
+                hp: i64::MAX,
+                earliest: 5000,
+                latest: 10000,
+            })
+            .with_rng(FakeRng {})
+            .run();
+        assert_eq!(EndReason::Enrage, result.end_reason);
+        assert_eq!(10000, result.duration);
+    }
+
+    #[test]
+    fn run_script() {
+        let mut actor = actor();
+        let true_thrust = actor.actions.get(&1).unwrap().clone();
+        let mut script = Script::default();
+        script.add(ScriptStep::new(&true_thrust, Some(1000)));
+        // The second step can't go before the GCD is back at 3.5s.
+        script.add(ScriptStep::new(&true_thrust, Some(2000)));
+        actor
+            .rotation
+            .set_script(script.with_mode(ScriptMode::Validate));
+        let result = Simulation::new()
+            .with_actor(actor)
+            .with_duration(6001)
+            .with_rng(FakeRng {})
+            .run();
+        let events: Vec<(SimTime, &CombatEventKind)> = result
+            .events
+            .iter()
+            .filter(|event| {
+                matches!(
+                    event.kind,
+                    CombatEventKind::ActionUsed { .. } | CombatEventKind::ScriptStepLate { .. }
+                )
+            })
+            .map(|event| (event.time, &event.kind))
+            .collect();
+        let used = |action_id| CombatEventKind::ActionUsed {
+            action_id,
+            name: "True Thrust".into(),
+        };
+        assert_eq!(
+            vec![
+                (1000, &used(1)),
+                (
+                    2000,
+                    &CombatEventKind::ScriptStepLate {
+                        action_id: 1,
+                        scheduled: Some(2000),
+                        ready_at: 3500,
+                    }
+                ),
+                (3500, &used(1)),
+                // The priority list takes over once the script is done.
+                (6000, &used(1)),
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn run_casts() {
+        let mut actor = actor();
+        let holy_spirit = Action {
+            id: 2,
+            name: "Holy Spirit".into(),
+            cast_time: Some(CastTime {
+                base: 3000,
+                speed: Stat::SpellSpeed,
+            }),
+            results: vec![
+                Arc::new(DoDirectDamage {
+                    action_id: 2,
+                    potency: 350,
+                    ..Default::default()
+                }),
+                Arc::new(StartGcd::default().with_speed(Stat::SpellSpeed)),
+            ],
+            ..Default::default()
+        };
+        let swiftcast = Action {
+            id: 3,
+            name: "Swiftcast".into(),
+            ogcd: true,
+            results: vec![
+                Arc::new(GiveStatusEffect {
+                    status: Status {
+                        name: "Swiftcast".into(),
+                        duration: 10000,
+                        flags: StatusFlags::new(&[StatusFlag::ExpireOnCast]),
+                        effects: vec![Arc::new(ModifySpecialStat {
+                            stat: SpecialStat::InstantCast,
+                            amount: 1,
+                        })],
+                    },
+                    target_source: true,
+                }),
+                Arc::new(StartRecast {
+                    action_id: 3,
+                    duration: 60000,
+                }),
+            ],
+            ..Default::default()
+        };
+        actor.rotation = Rotation::default();
+        actor.rotation.add(RotationEntry::new(&swiftcast));
+        actor.rotation.add(RotationEntry::new(&holy_spirit));
+        actor.actions.add(holy_spirit);
+        actor.actions.add(swiftcast);
+        let result = Simulation::new()
+            .with_actor(actor)
+            .with_duration(6101)
+            .with_rng(FakeRng {})
+            .run();
+        let events: Vec<(SimTime, &str)> = result
+            .events
+            .iter()
+            .filter_map(|event| {
+                let kind = match &event.kind {
+                    CombatEventKind::ActionUsed { name, .. } => name.as_str(),
+                    CombatEventKind::DamageDealt { .. } => "damage",
+                    CombatEventKind::GcdStarted { .. } => "gcd",
+                    CombatEventKind::CastStarted { .. } => "cast",
+                    CombatEventKind::CastCompleted { .. } => "cast completed",
+                    CombatEventKind::StatusExpired { .. } => "expired",
+                    _ => return None,
+                };
+                Some((event.time, kind))
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (0, "Swiftcast"),
+                // Swiftcast makes the first cast instant, and is used up by it.
+                (600, "Holy Spirit"),
+                (600, "expired"),
+                (600, "damage"),
+                (600, "gcd"),
+                // The GCD starts with the cast, but the actor is busy until the cast completes.
+                (3100, "Holy Spirit"),
+                (3100, "cast"),
+                (3100, "gcd"),
+                (6100, "cast completed"),
+                (6100, "damage"),
+                (6100, "Holy Spirit"),
+                (6100, "cast"),
+                (6100, "gcd"),
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn run_animation_lock() {
+        let mut actor = actor();
+        for id in 2..5 {
+            let ogcd = Action {
+                id,
+                name: format!("Weave {}", id),
+                ogcd: true,
+                results: vec![Arc::new(StartRecast {
+                    action_id: id,
+                    duration: 60000,
+                })],
+                ..Default::default()
+            };
+            actor.rotation.add(RotationEntry::new(&ogcd));
+            actor.actions.add(ogcd);
+        }
+        let used = |latency| {
+            Simulation::new()
+                .with_actor(actor.clone())
+                .with_duration(3000)
+                .with_latency(latency)
+                .with_rng(FakeRng {})
+                .run()
+                .events
+                .iter()
+                .filter_map(|event| match event.kind {
+                    CombatEventKind::ActionUsed { action_id, .. } => Some((event.time, action_id)),
+                    _ => None,
+                })
+                .collect::<Vec<(SimTime, u32)>>()
+        };
+        // Three oGCDs fit between two GCDs without latency, but clip the second GCD with it.
+        assert_eq!(
+            vec![(0, 1), (600, 2), (1200, 3), (1800, 4), (2500, 1)],
+            used(0)
+        );
+        assert_eq!(
+            vec![(0, 1), (700, 2), (1400, 3), (2100, 4), (2800, 1)],
+            used(100)
+        );
+    }
+
+    #[test]
+    fn run_preserves_combo() {
+        let combo_events = |preserves_combo| {
+            let mut actor = actor();
+            let true_thrust = Action {
+                id: 1,
+                name: "True Thrust".into(),
+                results: vec![
+                    Arc::new(DoDirectDamage {
+                        action_id: 1,
+                        potency: 290,
+                        ..Default::default()
+                    }),
+                    Arc::new(ApplyCombo(1)),
+                    Arc::new(StartGcd::default()),
+                ],
+                ..Default::default()
+            };
+            let geirskogul = Action {
+                id: 2,
+                name: "Geirskogul".into(),
+                ogcd: true,
+                results: vec![Arc::new(DoDirectDamage {
+                    action_id: 2,
+                    potency: 300,
+                    preserves_combo,
+                    ..Default::default()
+                })],
+                ..Default::default()
+            };
+            let vorpal_thrust = Action {
+                id: 3,
+                name: "Vorpal Thrust".into(),
+                results: vec![
+                    Arc::new(DoDirectDamage {
+                        action_id: 3,
+                        potency: 140,
+                        combo_action_id: Some(1),
+                        combo_potency: Some(350),
+                        combo_results: vec![Arc::new(GiveStatusEffect {
+                            status: Status {
+                                name: "Power Surge".into(),
+                                duration: 30000,
+                                ..Default::default()
+                            },
+                            target_source: true,
+                        })],
+                        ..Default::default()
+                    }),
+                    Arc::new(StartGcd::default()),
+                ],
+                ..Default::default()
+            };
+            let mut script = Script::default();
+            script.add(ScriptStep::new(&true_thrust, Some(0)));
+            script.add(ScriptStep::new(&geirskogul, Some(600)));
+            script.add(ScriptStep::new(&vorpal_thrust, Some(2500)));
+            actor.actions.add(true_thrust);
+            actor.actions.add(geirskogul);
+            actor.actions.add(vorpal_thrust);
+            actor
+                .rotation
+                .set_script(script.with_mode(ScriptMode::Validate));
+            Simulation::new()
+                .with_actor(actor)
+                .with_duration(2501)
+                .with_rng(FakeRng {})
+                .run()
+                .events
+                .into_iter()
+                .filter(|event| {
+                    matches!(
+                        event.kind,
+                        CombatEventKind::ComboConsumed { .. }
+                            | CombatEventKind::ComboMissed { .. }
+                            | CombatEventKind::StatusApplied { .. }
+                    )
+                })
+                .map(|event| event.kind)
+                .collect::<Vec<CombatEventKind>>()
+        };
+        // Damage from an action that doesn't preserve combos, unlike most oGCDs, breaks them, and
+        // the combo only results are left out.
+        assert_eq!(
+            vec![
+                CombatEventKind::ComboConsumed { action_id: 1 },
+                CombatEventKind::StatusApplied {
+                    name: "Power Surge".into(),
+                    expiration: 32500,
+                },
+            ],
+            combo_events(true)
+        );
+        assert_eq!(
+            vec![CombatEventKind::ComboMissed { action_id: 1 }],
+            combo_events(false)
+        );
+    }
+
+    #[test]
+    fn run_job_gauge() {
+        let mut actor = actor();
+        let gauge_action = |id, name: &str, amount| Action {
+            id,
+            name: name.into(),
+            results: vec![
+                Arc::new(ModifyGauge {
+                    name: "Cartridges".into(),
+                    amount,
+                    max: Some(2),
+                }),
+                Arc::new(StartGcd::default()),
+            ],
+            ..Default::default()
+        };
+        let solid_barrel = gauge_action(2, "Solid Barrel", 1);
+        let burst_strike = gauge_action(3, "Burst Strike", -1);
+        actor.rotation = Rotation::default();
+        actor
+            .rotation
+            .add(
+                RotationEntry::new(&burst_strike).with_condition(Arc::new(CheckGauge {
+                    name: "Cartridges".into(),
+                    comparison: Comparison::GreaterOrEqual,
+                    amount: 2,
+                })),
+            );
+        actor.rotation.add(RotationEntry::new(&solid_barrel));
+        actor.actions.add(solid_barrel);
+        actor.actions.add(burst_strike);
+        let used = Simulation::new()
+            .with_actor(actor)
+            .with_duration(10001)
+            .with_rng(FakeRng {})
+            .run()
+            .events
+            .into_iter()
+            .filter_map(|event| match event.kind {
+                CombatEventKind::ActionUsed { action_id, .. } => Some(action_id),
+                _ => None,
+            })
+            .collect::<Vec<u32>>();
+        // Burst Strike spends a cartridge whenever Solid Barrel has filled the gauge.
+        assert_eq!(vec![2, 2, 3, 2, 3], used);
+    }
+
+    fn damage_buff(
+        id: u32,
+        name: &str,
+        effect: Arc<dyn Apply + Send + Sync>,
+        target_source: bool,
+    ) -> Action {
+        Action {
+            id,
+            name: name.into(),
+            ogcd: true,
+            results: vec![
+                Arc::new(GiveStatusEffect {
+                    status: Status {
+                        name: name.into(),
+                        duration: 20000,
+                        effects: vec![effect],
+                        ..Default::default()
+                    },
+                    target_source,
+                }),
+                Arc::new(StartRecast {
+                    action_id: id,
+                    duration: 60000,
+                }),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn run_damage_multipliers() {
+        let unbuffed = Simulation::new()
+            .with_actor(actor())
+            .with_duration(1)
+            .with_rng(FakeRng {})
+            .run()
+            .total_damage;
+
+        let mut actor = actor();
+        let lance_charge = damage_buff(
+            2,
+            "Lance Charge",
+            Arc::new(ModifyDamageDealt { percent: 15 }),
+            true,
+        );
+        let vulnerability = damage_buff(
+            3,
+            "Vulnerability Up",
+            Arc::new(ModifyDamageTaken { percent: 10 }),
+            false,
+        );
+        let true_thrust = actor.actions.get(&1).unwrap().clone();
+        actor.rotation = Rotation::default();
+        actor.rotation.add(RotationEntry::new(&lance_charge));
+        actor.rotation.add(RotationEntry::new(&vulnerability));
+        actor.rotation.add(RotationEntry::new(&true_thrust));
+        actor.actions.add(lance_charge);
+        actor.actions.add(vulnerability);
+        let result = Simulation::new()
+            .with_actor(actor)
+            .with_duration(1201)
+            .with_rng(FakeRng {})
+            .run();
+        let hit = result
+            .events
+            .iter()
+            .find_map(|event| match &event.kind {
+                CombatEventKind::DamageDealt {
+                    amount,
+                    multipliers,
+                    buffs,
+                    ..
+                } => Some((*amount, multipliers.clone(), buffs.clone())),
+                _ => None,
+            })
+            .unwrap();
+        // The source's buffs floor before the target's debuffs.
+        assert_eq!(
+            (
+                unbuffed * 115 / 100 * 110 / 100,
+                vec![1.15, 1.1],
+                vec!["Lance Charge".to_string(), "Vulnerability Up".to_string()]
+            ),
+            hit
+        );
+    }
+
+    #[test]
+    fn run_rate_bonuses() {
+        // Rolls in the middle, so only the bonuses make the hit crit and direct hit.
+        struct MiddleRng {}
+        impl SimRng for MiddleRng {
+            fn random(&self) -> f64 {
+                0.5
+            }
+            fn random_from_range(&self, _low_inclusive: i64, _high_exclusive: i64) -> i64 {
+                100
+            }
+        }
+        let first_hit = |result: SimulationResult| {
+            result.events.iter().find_map(|event| match event.kind {
+                CombatEventKind::DamageDealt {
+                    critical_hit,
+                    direct_hit,
+                    ..
+                } => Some((critical_hit, direct_hit)),
+                _ => None,
+            })
+        };
+
+        let mut actor = actor();
+        actor.stats.set_base(Stat::CriticalHitRate, 380);
+        actor.stats.set_base(Stat::DirectHitRate, 380);
+        let unbuffed = Simulation::new()
+            .with_actor(actor.clone())
+            .with_duration(1)
+            .with_rng(MiddleRng {})
+            .run();
+        assert_eq!(Some((false, false)), first_hit(unbuffed));
+
+        // Chain Stratagem is a debuff that raises the crit rate of hits against the target.
+        let chain_stratagem = damage_buff(
+            2,
+            "Chain Stratagem",
+            Arc::new(ModifySpecialStat {
+                stat: SpecialStat::CriticalHitPercentBonus,
+                amount: 46,
+            }),
+            false,
+        );
+        let battle_voice = damage_buff(
+            3,
+            "Battle Voice",
+            Arc::new(ModifySpecialStat {
+                stat: SpecialStat::DirectHitPercentBonus,
+                amount: 51,
+            }),
+            true,
+        );
+        let true_thrust = actor.actions.get(&1).unwrap().clone();
+        actor.rotation = Rotation::default();
+        actor.rotation.add(RotationEntry::new(&chain_stratagem));
+        actor.rotation.add(RotationEntry::new(&battle_voice));
+        actor.rotation.add(RotationEntry::new(&true_thrust));
+        actor.actions.add(chain_stratagem);
+        actor.actions.add(battle_voice);
+        let buffed = Simulation::new()
+            .with_actor(actor)
+            .with_duration(1201)
+            .with_rng(MiddleRng {})
+            .run();
+        assert_eq!(Some((true, true)), first_hit(buffed));
+    }
+
+    fn auto_attack_times(result: &SimulationResult) -> Vec<SimTime> {
+        result
+            .events
+            .iter()
+            .filter_map(|event| match event.kind {
+                CombatEventKind::DamageDealt {
+                    action_id: AUTO_ATTACK_ID,
+                    ..
+                } => Some(event.time),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn run_auto_attacks() {
+        let mut actor = actor();
+        actor.stats.set_base(Stat::WeaponDelay, 2800);
+        let result = Simulation::new()
+            .with_actor(actor.clone())
+            .with_duration(6000)
+            .with_rng(FakeRng {})
+            .run();
+        assert_eq!(vec![0, 2800, 5600], auto_attack_times(&result));
+        assert_eq!(3, result.damage.by_action(AUTO_ATTACK_ID).unwrap().hits);
+
+        // Haste shortens the delay between swings.
+        actor.stats.set_base(Stat::Haste, 20);
+        let result = Simulation::new()
+            .with_actor(actor)
+            .with_duration(6000)
+            .with_rng(FakeRng {})
+            .run();
+        assert_eq!(vec![0, 2240, 4480], auto_attack_times(&result));
+    }
+
+    #[test]
+    fn run_auto_attacks_wait_for_casts() {
+        let mut actor = actor();
+        actor.stats.set_base(Stat::WeaponDelay, 2800);
+        let holy_spirit = Action {
+            id: 2,
+            name: "Holy Spirit".into(),
+            cast_time: Some(CastTime {
+                base: 3000,
+                speed: Stat::SpellSpeed,
+            }),
+            results: vec![
+                Arc::new(DoDirectDamage {
+                    action_id: 2,
+                    potency: 350,
+                    ..Default::default()
+                }),
+                Arc::new(StartGcd::default().with_speed(Stat::SpellSpeed)),
+            ],
+            ..Default::default()
+        };
+        actor.rotation = Rotation::default();
+        actor.rotation.add(RotationEntry::new(&holy_spirit));
+        actor.actions.add(holy_spirit);
+        let result = Simulation::new()
+            .with_actor(actor)
+            .with_duration(6001)
+            .with_rng(FakeRng {})
+            .run();
+        // Swings due mid-cast land as the cast completes.
+        assert_eq!(vec![0, 3000, 6000], auto_attack_times(&result));
+    }
+
+    #[test]
+    fn run_damage_over_time() {
+        let mut actor = actor();
+        let chaos_thrust = Action {
+            id: 2,
+            name: "Chaos Thrust".into(),
+            results: vec![
+                Arc::new(DoDamageOverTime {
+                    action_id: 2,
+                    name: "Chaos Thrust".into(),
+                    potency: 50,
+                    duration: 9000,
+                    attack_type: Default::default(),
+                }),
+                Arc::new(StartGcd::default()),
+            ],
+            ..Default::default()
+        };
+        let lance_charge = Action {
+            id: 3,
+            name: "Lance Charge".into(),
+            ogcd: true,
+            results: vec![
+                Arc::new(GiveStatusEffect {
+                    status: Status {
+                        name: "Lance Charge".into(),
+                        duration: 60000,
+                        effects: vec![Arc::new(ModifyStat {
+                            stat: Stat::AttackPower,
+                            amount: 1000,
+                        })],
+                        ..Default::default()
+                    },
+                    target_source: true,
+                }),
+                Arc::new(StartRecast {
+                    action_id: 3,
+                    duration: 60000,
+                }),
+            ],
+            ..Default::default()
+        };
+        let true_thrust = actor.actions.get(&1).unwrap().clone();
+        actor.rotation = Rotation::default();
+        actor.rotation.add(
+            RotationEntry::new(&chaos_thrust).with_condition(Arc::new(CheckNot(Arc::new(
+                CheckTargetHasDebuff("Chaos Thrust".into()),
+            )))),
+        );
+        actor.rotation.add(RotationEntry::new(&lance_charge));
+        actor.rotation.add(RotationEntry::new(&true_thrust));
+        actor.actions.add(chaos_thrust);
+        actor.actions.add(lance_charge);
+        let result = Simulation::new()
+            .with_actor(actor)
+            .with_duration(9001)
+            .with_rng(FakeRng {})
+            .run();
+        let ticks: Vec<(SimTime, i64)> = result
+            .events
+            .iter()
+            .filter_map(|event| match event.kind {
+                CombatEventKind::DamageOverTimeTicked { amount, .. } => Some((event.time, amount)),
+                _ => None,
+            })
+            .collect();
+        // The rng puts the server tick 100ms into the fight. Lance Charge goes up after Chaos
+        // Thrust, so the ticks keep the stats they were applied with.
+        let amount = ticks[0].1;
+        assert_eq!(vec![(100, amount), (3100, amount), (6100, amount)], ticks);
+        assert_eq!(3, result.damage.by_action(2).unwrap().hits);
+    }
+
+    #[test]
+    fn dps() {
+        let result = SimulationResult {
+            duration: 2000,
+            end_reason: EndReason::DurationElapsed,
+            seed: None,
+            total_damage: 5000,
+            damage: Damage::default(),
+            events: vec![],
+        };
+        assert_eq!(2500.0, result.dps());
+    }
+}
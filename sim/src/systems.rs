@@ -0,0 +1,496 @@
+use crate::actor::action::Action;
+use crate::actor::active_combos::ActiveCombos;
+use crate::actor::animation_lock::AnimationLock;
+use crate::actor::apply::{self, Apply};
+use crate::actor::auto_attack::{AutoAttack, AUTO_ATTACK_ID};
+use crate::actor::calc;
+use crate::actor::casting::{Cast, Casting};
+use crate::actor::damage::{Damage, HitType};
+use crate::actor::recast_expirations::RecastExpirations;
+use crate::actor::rotation::script::{ScriptAction, ScriptProgress};
+use crate::actor::rotation::ActorView;
+use crate::actor::stat::{Stat, Stats};
+use crate::actor::status_effect::status::StatusFlag;
+use crate::actor::status_effect::{self, StatusEffect, StatusEffects};
+use crate::actor::{ActorTuple, QueryActor, Target};
+use crate::combat_log::{CombatEventKind, CombatLog};
+use crate::sim::{SimState, SimTime};
+use bevy_app::AppBuilder;
+use bevy_ecs::prelude::*;
+use bevy_ecs::schedule::ShouldRun;
+
+fn tick(mut sim_state_query: Query<&mut SimState>) {
+    let mut sim_state = sim_state_query
+        .single_mut()
+        .expect("There should always be exactly one sim state.");
+
+    sim_state.tick();
+}
+
+fn sim_running(sim_state_query: Query<&SimState>) -> ShouldRun {
+    let sim_state = sim_state_query
+        .single()
+        .expect("There should always be exactly one sim state.");
+    if sim_state.is_finished() {
+        ShouldRun::No
+    } else {
+        ShouldRun::Yes
+    }
+}
+
+fn reset_stats(mut query: Query<&mut Stats>) {
+    for mut stats in query.iter_mut() {
+        stats.reset();
+    }
+}
+
+fn reset_active_combos(mut query: Query<&mut ActiveCombos>) {
+    for mut active_combos in query.iter_mut() {
+        active_combos.reset();
+    }
+}
+
+fn remove_expired_status_effects(
+    sim_state_query: Query<&SimState>,
+    mut log: ResMut<CombatLog>,
+    mut status_effects_query: Query<(Entity, &mut StatusEffects)>,
+) {
+    let sim_state = sim_state_query
+        .single()
+        .expect("There should always be exactly one sim state.");
+    let sim_time = sim_state.now();
+    for (entity, mut status_effects) in status_effects_query.iter_mut() {
+        // Effects expired early were logged when they were expired.
+        for effect in status_effects
+            .remove_expired(sim_time)
+            .into_iter()
+            .filter(|effect| !effect.force_expired)
+        {
+            log.record(
+                sim_time,
+                effect.source,
+                entity,
+                CombatEventKind::StatusExpired {
+                    name: effect.status.name,
+                },
+            );
+        }
+    }
+}
+
+#[derive(Debug)]
+struct StatusEffectApplyBundle {
+    status_effect: StatusEffect,
+    source: Entity,
+    target: Entity,
+}
+fn process_status_effects(
+    sim_state_query: Query<&SimState>,
+    mut log: ResMut<CombatLog>,
+    mut actor_query: QueryActor,
+) {
+    let sim = sim_state_query
+        .single()
+        .expect("There should always be exactly one sim state.");
+
+    let mut bundles = Vec::<StatusEffectApplyBundle>::default();
+    for (entity, _, _, _, _, _, status_effects, _, _, _) in actor_query.iter_mut() {
+        for effect in status_effects.iter() {
+            bundles.push(StatusEffectApplyBundle {
+                status_effect: effect.clone(),
+                source: effect.source,
+                target: entity,
+            });
+        }
+    }
+    for bundle in bundles {
+        bundle.status_effect.apply(
+            sim,
+            &mut log,
+            &mut actor_query,
+            bundle.source,
+            bundle.target,
+        );
+    }
+}
+
+struct ActionPerformBundle {
+    action: Action,
+    source_entity: Entity,
+    target_entity: Entity,
+}
+fn perform_actions(
+    mut sim_state_query: Query<&mut SimState>,
+    mut log: ResMut<CombatLog>,
+    mut actor_queries: QuerySet<(Query<ActorTuple, With<Target>>, QueryActor)>,
+    mut script_progress_query: Query<&mut ScriptProgress>,
+    mut casting_query: Query<(Entity, &mut Casting)>,
+    mut animation_lock_query: Query<&mut AnimationLock>,
+) {
+    let mut sim = sim_state_query
+        .single_mut()
+        .expect("There should always be exactly one sim state.");
+    let sim_time = sim.now();
+
+    // Casts that complete now apply their results before anyone picks their next action.
+    let mut completed = Vec::<(Entity, Cast)>::default();
+    for (entity, mut casting) in casting_query.iter_mut() {
+        if let Some(cast) = casting.complete(sim_time) {
+            completed.push((entity, cast));
+        }
+    }
+    for (entity, cast) in completed {
+        log.record(
+            sim_time,
+            entity,
+            cast.target,
+            CombatEventKind::CastCompleted {
+                action_id: cast.action.id,
+            },
+        );
+        cast.action
+            .complete_cast(&sim, &mut log, actor_queries.q1_mut(), entity, cast.target);
+    }
+
+    let (target_entity, _, _, _, _, _, target_status_effects, _, _, _) = actor_queries
+        .q0_mut()
+        .single_mut()
+        .expect("There should always be exactly one target.");
+    // The target's effects are copied since both queries can't be borrowed at once.
+    let target_status_effects = target_status_effects.clone();
+
+    let actor_query = actor_queries.q1_mut();
+    let mut perform_bundles = Vec::<ActionPerformBundle>::default();
+    for (
+        entity,
+        _,
+        actions,
+        rotation,
+        recast_expirations,
+        _,
+        status_effects,
+        stats,
+        active_combos,
+        job_gauge,
+    ) in actor_query.iter_mut()
+    {
+        let (_, casting) = casting_query
+            .get_mut(entity)
+            .expect("Every actor should have a casting component.");
+        let animation_lock = animation_lock_query
+            .get_mut(entity)
+            .expect("Every actor should have an animation lock.");
+        // Actors can't act while casting or locked by their last action.
+        if casting.is_casting() || animation_lock.is_locked(sim_time) {
+            continue;
+        }
+        let actor = ActorView {
+            sim_time,
+            entity,
+            recast_expirations: &recast_expirations,
+            active_combos: &active_combos,
+            status_effects: &status_effects,
+            stats: &stats,
+            job_gauge: &job_gauge,
+            target_status_effects: &target_status_effects,
+        };
+        let mut script_progress = script_progress_query
+            .get_mut(entity)
+            .expect("Every actor should have script progress.");
+        if let Some(step) = script_progress.late(rotation.script(), &actor) {
+            log.record(
+                sim_time,
+                entity,
+                target_entity,
+                CombatEventKind::ScriptStepLate {
+                    action_id: step.action_id,
+                    scheduled: step.at,
+                    ready_at: recast_expirations.ready_at(step.action_id, step.ogcd, sim_time),
+                },
+            );
+        }
+        // The script runs first, and holds the priority list back until it is done.
+        let next_action_id = match script_progress.next(rotation.script(), &actor) {
+            ScriptAction::Use(action_id) => Some(action_id),
+            ScriptAction::WaitUntil(at) => {
+                sim.schedule(at);
+                None
+            }
+            ScriptAction::WaitForReady => None,
+            ScriptAction::Done => rotation.get_next_action_id(&actor),
+        };
+        if let Some(action_id) = next_action_id {
+            if let Some(action) = actions.get(&action_id) {
+                perform_bundles.push(ActionPerformBundle {
+                    action: action.clone(),
+                    source_entity: entity,
+                    target_entity,
+                });
+            }
+        }
+    }
+
+    for bundle in perform_bundles {
+        log.record(
+            sim_time,
+            bundle.source_entity,
+            bundle.target_entity,
+            CombatEventKind::ActionUsed {
+                action_id: bundle.action.id,
+                name: bundle.action.name.clone(),
+            },
+        );
+        // The actor gets another chance once the lock ends, eg. to weave an oGCD.
+        let lock = bundle.action.animation_lock + sim.latency();
+        animation_lock_query
+            .get_mut(bundle.source_entity)
+            .expect("Every actor should have an animation lock.")
+            .lock(sim_time + lock);
+        sim.schedule(sim_time + lock);
+        log.record(
+            sim_time,
+            bundle.source_entity,
+            bundle.source_entity,
+            CombatEventKind::AnimationLockStarted { duration: lock },
+        );
+        let cast_duration = match actor_query.get_mut(bundle.source_entity) {
+            Ok((_, _, _, _, _, _, mut status_effects, stats, _, _)) => {
+                if bundle.action.is_instant_cast(&stats) {
+                    status_effects.expire_with_flag(
+                        StatusFlag::ExpireOnCast,
+                        sim_time,
+                        &mut log,
+                        bundle.source_entity,
+                    );
+                }
+                bundle.action.cast_duration(&stats)
+            }
+            Err(_) => 0,
+        };
+        if cast_duration == 0 {
+            bundle.action.perform(
+                &sim,
+                &mut log,
+                actor_query,
+                bundle.source_entity,
+                bundle.target_entity,
+            );
+            continue;
+        }
+        log.record(
+            sim_time,
+            bundle.source_entity,
+            bundle.target_entity,
+            CombatEventKind::CastStarted {
+                action_id: bundle.action.id,
+                duration: cast_duration,
+            },
+        );
+        bundle.action.start_cast(
+            &sim,
+            &mut log,
+            actor_query,
+            bundle.source_entity,
+            bundle.target_entity,
+        );
+        let completes_at = sim_time + cast_duration;
+        sim.schedule(completes_at);
+        let (_, mut casting) = casting_query
+            .get_mut(bundle.source_entity)
+            .expect("Every actor should have a casting component.");
+        casting.start(Cast {
+            action: bundle.action,
+            target: bundle.target_entity,
+            completes_at,
+        });
+    }
+}
+
+// Swings every auto-attack that is due, before anyone acts so a swing held back by a cast lands
+// as the cast completes. The next swing is due a full weapon delay after the held one lands.
+fn auto_attack(
+    mut sim_state_query: Query<&mut SimState>,
+    mut log: ResMut<CombatLog>,
+    target_query: Query<Entity, With<Target>>,
+    mut actor_query: QueryActor,
+    mut auto_attack_query: Query<(Entity, &mut AutoAttack, &Casting)>,
+) {
+    let mut sim = sim_state_query
+        .single_mut()
+        .expect("There should always be exactly one sim state.");
+    let sim_time = sim.now();
+    let target_entity = target_query
+        .single()
+        .expect("There should always be exactly one target.");
+
+    for (entity, mut auto_attack, casting) in auto_attack_query.iter_mut() {
+        if casting.is_casting_at(sim_time) || !auto_attack.is_due(sim_time) {
+            continue;
+        }
+        let multipliers =
+            status_effect::damage_multipliers(&sim, &mut actor_query, entity, target_entity);
+        let target_stats = apply::target_stats(&mut actor_query, target_entity);
+        let (potency, calculated_damage, delay) = match actor_query.get_mut(entity) {
+            Ok((_, job, _, _, _, _, _, stats, _, _)) => {
+                let potency = job.auto_attack_potency();
+                let delay = stats.get(Stat::WeaponDelay);
+                // Actors without a weapon delay, like the target, don't auto-attack.
+                if potency == 0 || delay <= 0 {
+                    continue;
+                }
+                (
+                    potency,
+                    calc::auto_attack(
+                        &sim,
+                        potency,
+                        *job,
+                        &stats.against(&target_stats),
+                        multipliers.values(),
+                    ),
+                    calc::auto_attack_delay(delay as SimTime, stats.get(Stat::Haste)),
+                )
+            }
+            Err(_) => continue,
+        };
+        if let Ok((_, _, _, _, _, mut damage, _, _, _, _)) = actor_query.get_mut(target_entity) {
+            damage.add(
+                entity,
+                AUTO_ATTACK_ID,
+                calculated_damage.amount,
+                HitType::new(calculated_damage.critical_hit, calculated_damage.direct_hit),
+            );
+        }
+        log.record(
+            sim_time,
+            entity,
+            target_entity,
+            CombatEventKind::DamageDealt {
+                action_id: AUTO_ATTACK_ID,
+                potency,
+                amount: calculated_damage.amount,
+                critical_hit: calculated_damage.critical_hit,
+                direct_hit: calculated_damage.direct_hit,
+                multipliers: multipliers.values(),
+                buffs: multipliers.names(),
+            },
+        );
+        let next = auto_attack.swing(sim_time, delay);
+        sim.schedule(next);
+    }
+}
+
+fn check_encounter(
+    mut sim_state_query: Query<&mut SimState>,
+    target_query: Query<&Damage, With<Target>>,
+) {
+    let mut sim = sim_state_query
+        .single_mut()
+        .expect("There should always be exactly one sim state.");
+    let damage = target_query
+        .single()
+        .expect("There should always be exactly one target.");
+    sim.check_target_damage(damage.total());
+}
+
+// Schedules the next update for every pending expiration so the tick can jump straight to it.
+fn schedule_events(
+    mut sim_state_query: Query<&mut SimState>,
+    actor_query: Query<(&RecastExpirations, &StatusEffects)>,
+) {
+    let mut sim = sim_state_query
+        .single_mut()
+        .expect("There should always be exactly one sim state.");
+    let sim_time = sim.now();
+    for (recast_expirations, status_effects) in actor_query.iter() {
+        let next_events = [
+            recast_expirations.next_expiration(sim_time),
+            status_effects.next_expiration(sim_time),
+        ];
+        for next_event in next_events.iter().flatten() {
+            sim.schedule(*next_event);
+        }
+        if status_effects.ticks(sim_time) {
+            let next_server_tick = sim.next_server_tick();
+            sim.schedule(next_server_tick);
+        }
+    }
+}
+
+#[derive(Debug, Hash, Eq, PartialEq, Clone, SystemLabel)]
+enum SimLabel {
+    Setup,
+    Calculate,
+    AutoAttack,
+    Execute,
+    Resolve,
+    Schedule,
+}
+
+#[derive(Debug, Hash, Eq, PartialEq, Clone, StageLabel)]
+enum SimStage {
+    Tick,
+    Update,
+}
+
+/// Registers the simulation systems, in the order they run each update. Stages are single threaded
+/// since every system depends on the one before it, and it keeps each run free of thread pools so
+/// many runs can execute side by side.
+pub(crate) fn add_systems(app: &mut AppBuilder) {
+    // Time advances in its own stage so every run criteria below sees the new timestamp.
+    app.add_stage(SimStage::Tick, SystemStage::single_threaded())
+        .add_stage_after(
+            SimStage::Tick,
+            SimStage::Update,
+            SystemStage::single_threaded(),
+        )
+        .add_system_to_stage(SimStage::Tick, tick.system())
+        .add_system_set_to_stage(
+            SimStage::Update,
+            SystemSet::new()
+                .label(SimLabel::Setup)
+                .with_run_criteria(sim_running.system())
+                .with_system(reset_stats.system())
+                .with_system(reset_active_combos.system())
+                .with_system(remove_expired_status_effects.system()),
+        )
+        .add_system_set_to_stage(
+            SimStage::Update,
+            SystemSet::new()
+                .label(SimLabel::Calculate)
+                .with_run_criteria(sim_running.system())
+                .with_system(process_status_effects.system())
+                .after(SimLabel::Setup),
+        )
+        .add_system_set_to_stage(
+            SimStage::Update,
+            SystemSet::new()
+                .label(SimLabel::AutoAttack)
+                .with_run_criteria(sim_running.system())
+                .with_system(auto_attack.system())
+                .after(SimLabel::Calculate),
+        )
+        .add_system_set_to_stage(
+            SimStage::Update,
+            SystemSet::new()
+                .label(SimLabel::Execute)
+                .with_run_criteria(sim_running.system())
+                .with_system(perform_actions.system())
+                .after(SimLabel::AutoAttack),
+        )
+        .add_system_set_to_stage(
+            SimStage::Update,
+            SystemSet::new()
+                .label(SimLabel::Resolve)
+                .with_run_criteria(sim_running.system())
+                .with_system(check_encounter.system())
+                .after(SimLabel::Execute),
+        )
+        .add_system_set_to_stage(
+            SimStage::Update,
+            SystemSet::new()
+                .label(SimLabel::Schedule)
+                .with_run_criteria(sim_running.system())
+                .with_system(schedule_events.system())
+                .after(SimLabel::Resolve),
+        );
+}
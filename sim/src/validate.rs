@@ -0,0 +1,476 @@
+use crate::actor::action::Actions;
+use crate::actor::rotation::script::ScriptMode;
+use crate::actor::rotation::Rotation;
+use crate::combat_log::{CombatEvent, CombatEventKind};
+use crate::sim::{DamageMode, SimTime};
+use crate::simulation::{ActorConfig, Simulation};
+use bevy_ecs::prelude::Entity;
+use serde::Serialize;
+use std::collections::HashMap;
+
+// TIME_LIMIT bounds a validation run, in case a script waits on a very long cooldown.
+const TIME_LIMIT: SimTime = 30 * 60 * 1000;
+
+/// ViolationKind is the rule a scripted action broke.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type")]
+pub enum ViolationKind {
+    // OnCooldown is an action scheduled before its recast, or the GCD, was over at `ready_at`.
+    OnCooldown {
+        ready_at: SimTime,
+    },
+    // Delayed is a step that could only go after its timestamp because earlier steps ran late.
+    Delayed {
+        scheduled: SimTime,
+    },
+    // MissingCombo is a combo action used without the combo from `combo_action_id`, named
+    // `combo_name`, active.
+    MissingCombo {
+        combo_action_id: u32,
+        combo_name: String,
+    },
+    // ClippedGcd is a GCD held back `delay` past `ready_at` by an oGCD woven before it.
+    ClippedGcd {
+        ready_at: SimTime,
+        delay: SimTime,
+    },
+    // AnimationLockOverlap is an action scheduled or used before the previous action's lock ended.
+    AnimationLockOverlap {
+        lock_end: SimTime,
+    },
+}
+
+/// Violation is a rule broken by an action of a scripted rotation, at the time it was scheduled or
+/// used.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation {
+    pub time: SimTime,
+    pub action_id: u32,
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: ViolationKind,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let seconds = |time: &SimTime| *time as f64 / 1000.0;
+        write!(f, "[{}s] {}: ", seconds(&self.time), self.name)?;
+        match &self.kind {
+            ViolationKind::OnCooldown { ready_at } => {
+                write!(f, "used on cooldown, ready at {}s", seconds(ready_at))
+            }
+            ViolationKind::Delayed { scheduled } => write!(
+                f,
+                "scheduled at {}s, but earlier steps ran late",
+                seconds(scheduled)
+            ),
+            ViolationKind::MissingCombo { combo_name, .. } => {
+                write!(f, "used without the combo from {}", combo_name)
+            }
+            ViolationKind::ClippedGcd { ready_at, delay } => write!(
+                f,
+                "GCD clipped by {}s after it was ready at {}s",
+                seconds(delay),
+                seconds(ready_at)
+            ),
+            ViolationKind::AnimationLockOverlap { lock_end } => write!(
+                f,
+                "used during an animation lock that ends at {}s",
+                seconds(lock_end)
+            ),
+        }
+    }
+}
+
+// validate runs the scripted steps of the actor's rotation on their own, without its priority
+// list, and reports every rule they break in the order they happened. latency is added to every
+// animation lock, as in a simulation.
+pub fn validate(actor: &ActorConfig, latency: SimTime) -> Vec<Violation> {
+    let mut rotation = Rotation::default();
+    rotation.set_script(
+        actor
+            .rotation
+            .script()
+            .clone()
+            .with_mode(ScriptMode::Validate),
+    );
+    let result = Simulation::new()
+        .with_actor(ActorConfig {
+            rotation,
+            ..actor.clone()
+        })
+        .with_duration(TIME_LIMIT)
+        .with_damage_mode(DamageMode::Expected)
+        .with_latency(latency)
+        .with_seed(0)
+        .run();
+    violations(&result.events, &actor.actions)
+}
+
+// violations checks the actions in a combat log against the recast, combo and animation lock
+// rules.
+pub fn violations(events: &[CombatEvent], actions: &Actions) -> Vec<Violation> {
+    let mut timelines = HashMap::<Entity, Timeline>::new();
+    let mut violations = Vec::new();
+    for event in events {
+        let timeline = timelines.entry(event.source).or_default();
+        if let Some((action_id, time, kind)) = timeline.check(event, actions) {
+            violations.push(Violation {
+                time,
+                action_id,
+                name: actions
+                    .get(&action_id)
+                    .map(|action| action.name.clone())
+                    .unwrap_or_default(),
+                kind,
+            });
+        }
+    }
+    violations
+}
+
+// Timeline is what the validator remembers about one actor's previous actions.
+#[derive(Default)]
+struct Timeline {
+    last_action_id: u32,
+    last_action_ogcd: bool,
+    lock_start: SimTime,
+    lock_end: SimTime,
+    gcd_ready: Option<SimTime>,
+}
+
+impl Timeline {
+    fn check(
+        &mut self,
+        event: &CombatEvent,
+        actions: &Actions,
+    ) -> Option<(u32, SimTime, ViolationKind)> {
+        match &event.kind {
+            CombatEventKind::ScriptStepLate {
+                action_id,
+                scheduled,
+                ready_at,
+            } => {
+                let kind = if *ready_at > event.time {
+                    ViolationKind::OnCooldown {
+                        ready_at: *ready_at,
+                    }
+                } else if scheduled
+                    .is_some_and(|scheduled| (self.lock_start..self.lock_end).contains(&scheduled))
+                {
+                    ViolationKind::AnimationLockOverlap {
+                        lock_end: self.lock_end,
+                    }
+                } else {
+                    ViolationKind::Delayed {
+                        scheduled: scheduled.unwrap_or(event.time),
+                    }
+                };
+                Some((*action_id, scheduled.unwrap_or(event.time), kind))
+            }
+            CombatEventKind::ActionUsed { action_id, .. } => {
+                let ogcd = actions.get(action_id).is_some_and(|action| action.ogcd);
+                let mut violation = None;
+                if event.time < self.lock_end {
+                    violation = Some(ViolationKind::AnimationLockOverlap {
+                        lock_end: self.lock_end,
+                    });
+                } else if let (false, true, Some(ready_at)) =
+                    (ogcd, self.last_action_ogcd, self.gcd_ready)
+                {
+                    // Only an oGCD woven before the GCD was ready can clip it.
+                    if self.lock_start < ready_at
+                        && self.lock_end > ready_at
+                        && event.time > ready_at
+                    {
+                        violation = Some(ViolationKind::ClippedGcd {
+                            ready_at,
+                            delay: event.time - ready_at,
+                        });
+                    }
+                }
+                self.last_action_id = *action_id;
+                self.last_action_ogcd = ogcd;
+                violation.map(|kind| (*action_id, event.time, kind))
+            }
+            CombatEventKind::AnimationLockStarted { duration } => {
+                self.lock_start = event.time;
+                self.lock_end = event.time + duration;
+                None
+            }
+            CombatEventKind::GcdStarted { duration } => {
+                self.gcd_ready = Some(event.time + duration);
+                None
+            }
+            CombatEventKind::ComboMissed { action_id } => Some((
+                self.last_action_id,
+                event.time,
+                ViolationKind::MissingCombo {
+                    combo_action_id: *action_id,
+                    combo_name: actions
+                        .get(action_id)
+                        .map(|action| action.name.clone())
+                        .unwrap_or_default(),
+                },
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::action::Action;
+    use crate::actor::apply::{ApplyCombo, DoDirectDamage, StartGcd, StartRecast};
+    use crate::actor::calc::lookup::Job;
+    use crate::actor::rotation::script::{Script, ScriptStep};
+    use crate::actor::stat::Stats;
+    use std::sync::Arc;
+
+    fn actions() -> Actions {
+        let mut actions = Actions::default();
+        actions.add(Action {
+            id: 1,
+            name: "True Thrust".into(),
+            results: vec![Arc::new(ApplyCombo(1)), Arc::new(StartGcd::default())],
+            ..Default::default()
+        });
+        actions.add(Action {
+            id: 2,
+            name: "Vorpal Thrust".into(),
+            results: vec![
+                Arc::new(DoDirectDamage {
+                    action_id: 2,
+                    potency: 140,
+                    combo_action_id: Some(1),
+                    combo_potency: Some(350),
+                    ..Default::default()
+                }),
+                Arc::new(StartGcd::default()),
+            ],
+            ..Default::default()
+        });
+        actions.add(Action {
+            id: 3,
+            name: "Life Surge".into(),
+            ogcd: true,
+            results: vec![Arc::new(StartRecast {
+                action_id: 3,
+                duration: 45000,
+            })],
+            ..Default::default()
+        });
+        actions
+    }
+
+    fn used(time: SimTime, action_id: u32) -> CombatEvent {
+        CombatEvent {
+            time,
+            source: Entity::new(1),
+            target: Entity::new(2),
+            kind: CombatEventKind::ActionUsed {
+                action_id,
+                name: String::new(),
+            },
+        }
+    }
+
+    fn locked(time: SimTime) -> CombatEvent {
+        CombatEvent {
+            time,
+            source: Entity::new(1),
+            target: Entity::new(1),
+            kind: CombatEventKind::AnimationLockStarted { duration: 600 },
+        }
+    }
+
+    fn gcd_started(time: SimTime) -> CombatEvent {
+        CombatEvent {
+            time,
+            source: Entity::new(1),
+            target: Entity::new(1),
+            kind: CombatEventKind::GcdStarted { duration: 2500 },
+        }
+    }
+
+    #[test]
+    fn animation_lock() {
+        let events = vec![
+            used(0, 1),
+            locked(0),
+            gcd_started(0),
+            used(300, 3),
+            locked(300),
+            used(2000, 3),
+            locked(2000),
+            used(2600, 1),
+            locked(2600),
+            gcd_started(2600),
+        ];
+        assert_eq!(
+            vec![
+                Violation {
+                    time: 300,
+                    action_id: 3,
+                    name: "Life Surge".into(),
+                    kind: ViolationKind::AnimationLockOverlap { lock_end: 600 },
+                },
+                Violation {
+                    time: 2600,
+                    action_id: 1,
+                    name: "True Thrust".into(),
+                    kind: ViolationKind::ClippedGcd {
+                        ready_at: 2500,
+                        delay: 100,
+                    },
+                },
+            ],
+            violations(&events, &actions())
+        );
+    }
+
+    #[test]
+    fn weave_without_clipping() {
+        let events = vec![
+            used(0, 1),
+            locked(0),
+            gcd_started(0),
+            used(1000, 3),
+            locked(1000),
+            used(2500, 1),
+            locked(2500),
+            gcd_started(2500),
+        ];
+        assert!(violations(&events, &actions()).is_empty());
+    }
+
+    #[test]
+    fn validate_script() {
+        let actions = actions();
+        let mut script = Script::default();
+        for (name, at) in [
+            ("Vorpal Thrust", 0),
+            ("Life Surge", 1000),
+            ("True Thrust", 2500),
+            ("Life Surge", 3000),
+            ("True Thrust", 4000),
+        ] {
+            script.add(ScriptStep::new(actions.find(name).unwrap(), Some(at)));
+        }
+        let mut rotation = Rotation::default();
+        rotation.set_script(script);
+        let actor = ActorConfig {
+            job: Job::DRG,
+            actions,
+            rotation,
+            stats: Stats::default(),
+        };
+        assert_eq!(
+            vec![
+                Violation {
+                    time: 0,
+                    action_id: 2,
+                    name: "Vorpal Thrust".into(),
+                    kind: ViolationKind::MissingCombo {
+                        combo_action_id: 1,
+                        combo_name: "True Thrust".into(),
+                    },
+                },
+                Violation {
+                    time: 3000,
+                    action_id: 3,
+                    name: "Life Surge".into(),
+                    kind: ViolationKind::OnCooldown { ready_at: 46000 },
+                },
+                // Once Life Surge is back, its lock holds the delayed True Thrust back until 46.6s.
+                Violation {
+                    time: 4000,
+                    action_id: 1,
+                    name: "True Thrust".into(),
+                    kind: ViolationKind::Delayed { scheduled: 4000 },
+                },
+            ],
+            validate(&actor, 0)
+        );
+    }
+
+    #[test]
+    fn validate_animation_lock() {
+        let actions = actions();
+        let mut script = Script::default();
+        for (name, at) in [
+            ("True Thrust", 0),
+            ("Life Surge", 2000),
+            ("Vorpal Thrust", 2500),
+        ] {
+            script.add(ScriptStep::new(actions.find(name).unwrap(), Some(at)));
+        }
+        let mut rotation = Rotation::default();
+        rotation.set_script(script);
+        let actor = ActorConfig {
+            job: Job::DRG,
+            actions,
+            rotation,
+            stats: Stats::default(),
+        };
+        assert_eq!(
+            vec![
+                // Life Surge, with latency, locks the actor until 2.7s.
+                Violation {
+                    time: 2500,
+                    action_id: 2,
+                    name: "Vorpal Thrust".into(),
+                    kind: ViolationKind::AnimationLockOverlap { lock_end: 2700 },
+                },
+                Violation {
+                    time: 2700,
+                    action_id: 2,
+                    name: "Vorpal Thrust".into(),
+                    kind: ViolationKind::ClippedGcd {
+                        ready_at: 2500,
+                        delay: 200,
+                    },
+                },
+            ],
+            validate(&actor, 100)
+        );
+    }
+
+    #[test]
+    fn display() {
+        let violation = Violation {
+            time: 3000,
+            action_id: 3,
+            name: "Life Surge".into(),
+            kind: ViolationKind::OnCooldown { ready_at: 46000 },
+        };
+        assert_eq!(
+            "[3s] Life Surge: used on cooldown, ready at 46s",
+            violation.to_string()
+        );
+        assert_eq!(
+            serde_json::json!({
+                "time": 3000,
+                "action_id": 3,
+                "name": "Life Surge",
+                "type": "OnCooldown",
+                "ready_at": 46000,
+            }),
+            serde_json::to_value(&violation).unwrap()
+        );
+        let violation = Violation {
+            time: 0,
+            action_id: 2,
+            name: "Vorpal Thrust".into(),
+            kind: ViolationKind::MissingCombo {
+                combo_action_id: 1,
+                combo_name: "True Thrust".into(),
+            },
+        };
+        assert_eq!(
+            "[0s] Vorpal Thrust: used without the combo from True Thrust",
+            violation.to_string()
+        );
+    }
+}
@@ -0,0 +1,392 @@
+use crate::actor::action::{Actions, DEFAULT_ANIMATION_LOCK};
+use crate::actor::apply::DEFAULT_GCD;
+use crate::actor::calc::AttackType;
+use crate::actor::stat::Stat;
+use crate::registry::{ActionDefinition, CastDefinition, ResultDefinition};
+use crate::sim::SimTime;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// ActionRecord is one action from the xivapi dumps the app ships in `app/data`. Columns the sim
+/// has no use for, like the icon and description, are ignored.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ActionRecord {
+    #[serde(rename = "ID")]
+    pub id: u32,
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Recast100ms")]
+    pub recast_100ms: u64,
+    #[serde(rename = "Cast100ms")]
+    pub cast_100ms: u64,
+    // action_combo_target_id is the action this one continues a combo from, or 0.
+    #[serde(rename = "ActionComboTargetID")]
+    pub action_combo_target_id: u32,
+    // preserves_combo is 1 for actions that don't break an active combo, eg. oGCDs.
+    #[serde(rename = "PreservesCombo")]
+    pub preserves_combo: u8,
+    #[serde(rename = "ActionCategoryTargetID")]
+    pub action_category_target_id: u32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ActionCategory {
+    Spell,
+    Weaponskill,
+    Ability,
+}
+
+impl ActionRecord {
+    // category is None for categories the dumps aren't filtered to, eg. auto-attacks.
+    pub fn category(&self) -> Option<ActionCategory> {
+        match self.action_category_target_id {
+            2 => Some(ActionCategory::Spell),
+            3 => Some(ActionCategory::Weaponskill),
+            4 => Some(ActionCategory::Ability),
+            _ => None,
+        }
+    }
+
+    // Spells and weaponskills are on the GCD, abilities are not.
+    pub fn ogcd(&self) -> bool {
+        self.category() == Some(ActionCategory::Ability)
+    }
+
+    // speed is the stat that shortens the action's GCD.
+    pub fn speed(&self) -> Stat {
+        match self.category() {
+            Some(ActionCategory::Spell) => Stat::SpellSpeed,
+            _ => Stat::SkillSpeed,
+        }
+    }
+
+    pub fn recast(&self) -> SimTime {
+        self.recast_100ms * 100
+    }
+
+    // cast is None for instant actions.
+    pub fn cast(&self) -> Option<CastDefinition> {
+        match self.cast_100ms {
+            0 => None,
+            cast_100ms => Some(CastDefinition {
+                duration: cast_100ms * 100,
+                speed: self.speed(),
+            }),
+        }
+    }
+
+    pub fn combo_action_id(&self) -> Option<u32> {
+        match self.action_combo_target_id {
+            0 => None,
+            id => Some(id),
+        }
+    }
+}
+
+/// ActionOverlay is what the dumps don't say about an action: its potencies and effects.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ActionOverlay {
+    pub potency: Option<i64>,
+    pub combo_potency: Option<i64>,
+    #[serde(default)]
+    pub attack_type: AttackType,
+    // animation_lock replaces the default lock for actions that lock longer, eg. jumps.
+    pub animation_lock: Option<SimTime>,
+    // results are applied after the damage, before the combo and recast.
+    #[serde(default)]
+    pub results: Vec<ResultDefinition>,
+    // combo_results are applied after the damage only when it consumes the combo, eg. Disembowel's
+    // buff.
+    #[serde(default)]
+    pub combo_results: Vec<ResultDefinition>,
+}
+
+// skeletons turns records into action definitions with the ids, GCD or oGCD, recasts and combo
+// links from the dumps. Overlays, keyed by action name, add potencies and effects.
+pub fn skeletons(
+    records: &[ActionRecord],
+    overlays: &HashMap<String, ActionOverlay>,
+) -> Vec<ActionDefinition> {
+    let combo_starters: HashSet<u32> = records
+        .iter()
+        .filter_map(ActionRecord::combo_action_id)
+        .collect();
+    let no_overlay = ActionOverlay::default();
+    records
+        .iter()
+        .map(|record| {
+            let overlay = overlays.get(&record.name).unwrap_or(&no_overlay);
+            let mut results = vec![];
+            // Combo actions without an overlay potency still hit for 0, to keep their combo link.
+            if overlay.potency.is_some() || record.combo_action_id().is_some() {
+                let combo_action_id = record.combo_action_id();
+                results.push(ResultDefinition::DoDirectDamage {
+                    potency: overlay.potency.unwrap_or(0),
+                    combo_action_id,
+                    combo_potency: combo_action_id.and(overlay.combo_potency),
+                    attack_type: overlay.attack_type,
+                    preserves_combo: record.preserves_combo != 0,
+                    combo_results: overlay.combo_results.clone(),
+                });
+            }
+            results.extend(overlay.results.iter().cloned());
+            if combo_starters.contains(&record.id) {
+                results.push(ResultDefinition::ApplyCombo { action_id: None });
+            }
+            if record.ogcd() {
+                results.push(ResultDefinition::StartRecast {
+                    action_id: None,
+                    duration: record.recast(),
+                });
+            } else {
+                results.push(ResultDefinition::StartGcd {
+                    duration: DEFAULT_GCD,
+                    speed: record.speed(),
+                });
+                // GCDs with a longer recast of their own, eg. Gnashing Fang.
+                if record.recast() > DEFAULT_GCD {
+                    results.push(ResultDefinition::StartRecast {
+                        action_id: None,
+                        duration: record.recast(),
+                    });
+                }
+            }
+            ActionDefinition {
+                id: record.id,
+                name: record.name.clone(),
+                ogcd: record.ogcd(),
+                cast: record.cast(),
+                animation_lock: overlay.animation_lock.unwrap_or(DEFAULT_ANIMATION_LOCK),
+                results,
+            }
+        })
+        .collect()
+}
+
+// import_actions reads a xivapi dump and a JSON object of overlays keyed by action name.
+pub fn import_actions(records: &str, overlays: &str) -> Result<Actions, serde_json::Error> {
+    let records: Vec<ActionRecord> = serde_json::from_str(records)?;
+    let overlays: HashMap<String, ActionOverlay> = serde_json::from_str(overlays)?;
+    let mut actions = Actions::default();
+    for definition in skeletons(&records, &overlays) {
+        actions.add(definition.to_action());
+    }
+    Ok(actions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: u32, name: &str, category: u32, recast_100ms: u64, combo: u32) -> ActionRecord {
+        ActionRecord {
+            id,
+            name: name.into(),
+            recast_100ms,
+            cast_100ms: 0,
+            action_combo_target_id: combo,
+            preserves_combo: 0,
+            action_category_target_id: category,
+        }
+    }
+
+    #[test]
+    fn parse_dumps() {
+        for dump in [
+            include_str!("../../app/data/PLD.json"),
+            include_str!("../../app/data/drg.json"),
+            include_str!("../../app/data/gnb.json"),
+        ] {
+            let records: Vec<ActionRecord> = serde_json::from_str(dump).unwrap();
+            assert!(records.iter().all(|record| record.category().is_some()));
+            assert!(import_actions(dump, "{}").is_ok());
+        }
+    }
+
+    #[test]
+    fn ogcd() {
+        assert!(record(83, "Life Surge", 4, 450, 0).ogcd());
+        assert!(!record(75, "True Thrust", 3, 25, 0).ogcd());
+        assert!(!record(7384, "Holy Spirit", 2, 25, 0).ogcd());
+    }
+
+    #[test]
+    fn speed() {
+        assert_eq!(
+            Stat::SkillSpeed,
+            record(75, "True Thrust", 3, 25, 0).speed()
+        );
+        assert_eq!(
+            Stat::SpellSpeed,
+            record(7384, "Holy Spirit", 2, 25, 0).speed()
+        );
+    }
+
+    #[test]
+    fn cast() {
+        assert!(record(75, "True Thrust", 3, 25, 0).cast().is_none());
+        let mut holy_spirit = record(7384, "Holy Spirit", 2, 25, 0);
+        holy_spirit.cast_100ms = 15;
+        let cast = holy_spirit.cast().unwrap();
+        assert_eq!(1500, cast.duration);
+        assert_eq!(Stat::SpellSpeed, cast.speed);
+    }
+
+    #[test]
+    fn skeleton_results() {
+        let records = vec![
+            record(75, "True Thrust", 3, 25, 0),
+            record(78, "Vorpal Thrust", 3, 25, 75),
+            record(83, "Life Surge", 4, 450, 0),
+            record(16146, "Gnashing Fang", 3, 300, 0),
+        ];
+        let mut overlays = HashMap::new();
+        overlays.insert(
+            "Vorpal Thrust".to_string(),
+            ActionOverlay {
+                potency: Some(140),
+                combo_potency: Some(350),
+                ..Default::default()
+            },
+        );
+        let definitions = skeletons(&records, &overlays);
+
+        assert!(matches!(
+            definitions[0].results[..],
+            [
+                ResultDefinition::ApplyCombo { action_id: None },
+                ResultDefinition::StartGcd {
+                    duration: 2500,
+                    speed: Stat::SkillSpeed
+                }
+            ]
+        ));
+        assert!(matches!(
+            definitions[1].results[..],
+            [
+                ResultDefinition::DoDirectDamage {
+                    potency: 140,
+                    combo_action_id: Some(75),
+                    combo_potency: Some(350),
+                    preserves_combo: false,
+                    ..
+                },
+                ResultDefinition::StartGcd {
+                    duration: 2500,
+                    speed: Stat::SkillSpeed
+                }
+            ]
+        ));
+        assert!(definitions[2].ogcd);
+        assert!(matches!(
+            definitions[2].results[..],
+            [ResultDefinition::StartRecast {
+                action_id: None,
+                duration: 45000
+            }]
+        ));
+        assert!(matches!(
+            definitions[3].results[..],
+            [
+                ResultDefinition::StartGcd {
+                    duration: 2500,
+                    speed: Stat::SkillSpeed
+                },
+                ResultDefinition::StartRecast {
+                    action_id: None,
+                    duration: 30000
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn skeleton_combo_results() {
+        let records = vec![
+            record(75, "True Thrust", 3, 25, 0),
+            record(87, "Disembowel", 3, 25, 75),
+        ];
+        let overlays: HashMap<String, ActionOverlay> = serde_json::from_str(
+            r#"{"Disembowel": {"potency": 100, "combo_potency": 240, "combo_results": [
+                {"type": "GiveStatusEffect", "target_source": true,
+                 "status": {"name": "Disembowel", "duration": 30000}}
+            ]}}"#,
+        )
+        .unwrap();
+        let definitions = skeletons(&records, &overlays);
+
+        // The buff is part of the damage, so that it only applies with the combo.
+        assert!(matches!(
+            &definitions[1].results[..],
+            [
+                ResultDefinition::DoDirectDamage {
+                    combo_action_id: Some(75),
+                    combo_results,
+                    ..
+                },
+                ResultDefinition::StartGcd { .. }
+            ] if matches!(combo_results[..], [ResultDefinition::GiveStatusEffect { .. }])
+        ));
+    }
+
+    #[test]
+    fn skeleton_combo_links() {
+        let mut savage_claw = record(16147, "Savage Claw", 3, 300, 16146);
+        savage_claw.preserves_combo = 1;
+        let records = vec![
+            record(16137, "Keen Edge", 3, 25, 0),
+            record(16139, "Brutal Shell", 3, 25, 16137),
+            record(16141, "Demon Slice", 3, 25, 0),
+            record(16149, "Demon Slaughter", 3, 25, 16141),
+            savage_claw,
+        ];
+        let mut overlays = HashMap::new();
+        overlays.insert(
+            "Demon Slaughter".to_string(),
+            ActionOverlay {
+                potency: Some(100),
+                ..Default::default()
+            },
+        );
+        let definitions = skeletons(&records, &overlays);
+
+        // Without an overlay, or a combo potency in it, the combo link is kept.
+        assert!(matches!(
+            definitions[1].results[..],
+            [
+                ResultDefinition::DoDirectDamage {
+                    potency: 0,
+                    combo_action_id: Some(16137),
+                    combo_potency: None,
+                    preserves_combo: false,
+                    ..
+                },
+                ResultDefinition::StartGcd { .. }
+            ]
+        ));
+        assert!(matches!(
+            definitions[3].results[..],
+            [
+                ResultDefinition::DoDirectDamage {
+                    potency: 100,
+                    combo_action_id: Some(16141),
+                    combo_potency: None,
+                    ..
+                },
+                ResultDefinition::StartGcd { .. }
+            ]
+        ));
+        assert!(matches!(
+            definitions[4].results[..],
+            [
+                ResultDefinition::DoDirectDamage {
+                    combo_action_id: Some(16146),
+                    preserves_combo: true,
+                    ..
+                },
+                ..
+            ]
+        ));
+    }
+}